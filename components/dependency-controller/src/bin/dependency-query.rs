@@ -0,0 +1,122 @@
+use std::{
+    fs,
+    io::{self, Read},
+    path::{Path, PathBuf},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use anyhow::{Context, Result};
+use clap::{Parser, Subcommand, ValueEnum};
+use dependency_controller::query;
+
+/// Answers questions about the dependency edges learned by the
+/// dependency-learner filter.
+#[derive(Debug, Parser)]
+struct Args {
+    /// Read edges from a JSON lines file of edge reports or single edges ("-"
+    /// for stdin).
+    #[arg(long = "jsonl", required_unless_present_any = ["configmaps", "collector"])]
+    jsonl: Vec<PathBuf>,
+    /// Read edges from a YAML file of dependency configmaps, e.g. from
+    /// `kubectl get configmap -A -l app.kubernetes.io/managed-by=dependency-controller
+    /// -o yaml` ("-" for stdin).
+    #[arg(long)]
+    configmaps: Vec<PathBuf>,
+    /// Fetch the edges a dependency-controller collected, e.g.
+    /// `http://localhost:8080/edges`.
+    #[arg(long)]
+    collector: Vec<String>,
+    #[arg(long, short, value_enum, default_value_t = Output::Table)]
+    output: Output,
+    #[command(subcommand)]
+    query: Query,
+}
+
+#[derive(Debug, Subcommand)]
+enum Query {
+    /// Every edge.
+    All,
+    /// Edges calling an upstream, named by cluster, host, service or
+    /// `<service>.<namespace>`.
+    Callers { upstream: String },
+    /// Edges of the workloads of a namespace.
+    Deps { namespace: String },
+    /// Edges not seen in the given number of days.
+    Stale {
+        #[arg(long = "days", value_name = "DAYS", value_parser = parse_days)]
+        age: Duration,
+    },
+}
+
+#[derive(Debug, Clone, Copy, ValueEnum)]
+enum Output {
+    Table,
+    Json,
+    Dot,
+    Mermaid,
+}
+
+/// Parses a number of days, rejecting ones too long for a [`Duration`] in
+/// seconds.
+fn parse_days(days: &str) -> Result<Duration, String> {
+    let days: u64 = days.parse().map_err(|err| format!("{}", err))?;
+    days.checked_mul(86_400)
+        .map(Duration::from_secs)
+        .ok_or_else(|| format!("{} days is too long", days))
+}
+
+fn read(path: &Path) -> Result<String> {
+    if path.as_os_str() == "-" {
+        let mut raw = String::new();
+        io::stdin()
+            .read_to_string(&mut raw)
+            .context("failed to read stdin")?;
+        Ok(raw)
+    } else {
+        fs::read_to_string(path).with_context(|| format!("failed to read {}", path.display()))
+    }
+}
+
+#[tokio::main]
+async fn main() -> Result<()> {
+    env_logger::init();
+    let args = Args::parse();
+
+    let mut edges = Vec::new();
+    for path in &args.jsonl {
+        edges.extend(
+            query::parse_json_lines(&read(path)?)
+                .with_context(|| format!("failed to load {}", path.display()))?,
+        );
+    }
+    for path in &args.configmaps {
+        edges.extend(
+            query::parse_configmaps(&read(path)?)
+                .with_context(|| format!("failed to load {}", path.display()))?,
+        );
+    }
+    for url in &args.collector {
+        edges.extend(query::fetch(url).await?);
+    }
+
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs();
+    let mut edges = query::dedup(edges);
+    edges.retain(|edge| match &args.query {
+        Query::All => true,
+        Query::Callers { upstream } => query::calls(&edge.edge, upstream),
+        Query::Deps { namespace } => query::in_namespace(&edge.edge, namespace),
+        Query::Stale { age } => query::stale(edge, now, *age),
+    });
+
+    let rendered = match args.output {
+        Output::Table => query::render_table(&edges),
+        Output::Json => query::render_json(&edges)?,
+        Output::Dot => query::render_dot(&edges),
+        Output::Mermaid => query::render_mermaid(&edges),
+    };
+    print!("{}", rendered);
+    Ok(())
+}
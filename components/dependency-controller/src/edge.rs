@@ -0,0 +1,40 @@
+pub use dependency_edge::{DependencyEdge, Direction, EdgeReport, ReportedEdge};
+
+/// Downstream workload identity, as carried by its SPIFFE ID.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Identity {
+    pub namespace: String,
+    pub service_account: String,
+}
+
+impl Identity {
+    /// Returns `None` for edges without a SPIFFE downstream.
+    pub fn of(edge: &DependencyEdge) -> Option<Self> {
+        Some(Self {
+            namespace: edge.downstream.namespace.clone()?,
+            service_account: edge.downstream.service_account.clone()?,
+        })
+    }
+}
+
+/// An edge reduced to what egress generation cares about.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Dependency {
+    pub downstream: Identity,
+    /// Host of the upstream service, e.g. `server.ns.svc.cluster.local`.
+    pub host: String,
+}
+
+impl Dependency {
+    /// Returns `None` for edges without a SPIFFE downstream or an outbound
+    /// upstream cluster, which cannot be turned into egress rules.
+    pub fn of(edge: &DependencyEdge) -> Option<Self> {
+        if edge.upstream.direction != Some(Direction::Outbound) {
+            return None;
+        }
+        Some(Self {
+            downstream: Identity::of(edge)?,
+            host: edge.upstream.host.clone()?,
+        })
+    }
+}
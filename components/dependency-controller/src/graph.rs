@@ -0,0 +1,27 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+use crate::edge::{Dependency, Identity};
+
+/// Egress hosts learned for each downstream identity.
+#[derive(Debug, Clone, Default)]
+pub struct DependencyGraph {
+    egress: BTreeMap<Identity, BTreeSet<String>>,
+}
+
+impl DependencyGraph {
+    /// Adds the dependency, returning whether it was not already known.
+    pub fn insert(&mut self, dependency: Dependency) -> bool {
+        self.egress
+            .entry(dependency.downstream)
+            .or_default()
+            .insert(dependency.host)
+    }
+
+    pub fn hosts(&self, identity: &Identity) -> Option<&BTreeSet<String>> {
+        self.egress.get(identity)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&Identity, &BTreeSet<String>)> {
+        self.egress.iter()
+    }
+}
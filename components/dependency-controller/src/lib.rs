@@ -0,0 +1,9 @@
+//! Turns the dependency edges reported by the `dependency-learner` filter into
+//! Istio `Sidecar` resources that only import the learned egress hosts.
+
+pub mod edge;
+pub mod graph;
+pub mod query;
+pub mod server;
+pub mod sidecar;
+pub mod store;
@@ -0,0 +1,107 @@
+use std::{
+    fs::File,
+    io::{self, BufRead, BufReader},
+    net::SocketAddr,
+    path::PathBuf,
+    sync::Arc,
+};
+
+use anyhow::{Context, Result};
+use clap::Parser;
+use dependency_controller::{
+    edge::EdgeReport,
+    server::{self, Collector, CollectorOptions},
+    sidecar::{SidecarGenerator, SidecarOptions},
+    store::{ConfigMapStore, DEFAULT_CONFIGMAP_NAME},
+};
+use kube::Client;
+use log::info;
+
+/// Generates Istio Sidecar resources from the edges reported by the
+/// dependency-learner filter.
+#[derive(Debug, Parser)]
+struct Args {
+    /// Read edge reports, one JSON report per line, from a file ("-" for
+    /// stdin), reconcile once and exit.
+    #[arg(long, conflicts_with = "listen", required_unless_present = "listen")]
+    input: Option<PathBuf>,
+    /// Accept edge reports POSTed by the filter on this address.
+    #[arg(long)]
+    listen: Option<SocketAddr>,
+    /// Path edge reports are POSTed to.
+    #[arg(long, default_value = "/edges")]
+    path: String,
+    /// Print the generated sidecars as YAML instead of applying them, and
+    /// leave the dependency configmaps untouched.
+    #[arg(long)]
+    dry_run: bool,
+    /// Name of the configmap the dependency graph is merged into in each
+    /// downstream namespace.
+    #[arg(long, default_value = DEFAULT_CONFIGMAP_NAME)]
+    configmap_name: String,
+    /// Egress host every generated sidecar imports besides the learned ones.
+    #[arg(long = "base-host", default_values = ["./*", "istio-system/*"])]
+    base_hosts: Vec<String>,
+    /// Prefix of generated sidecar names, followed by the service account.
+    #[arg(long, default_value = "learned-")]
+    name_prefix: String,
+    /// Edges kept in memory for GETs of the report path. The least recently
+    /// seen are dropped beyond it.
+    #[arg(long, default_value_t = CollectorOptions::default().max_edges)]
+    max_edges: usize,
+}
+
+#[tokio::main]
+async fn main() -> Result<()> {
+    env_logger::init();
+    let args = Args::parse();
+
+    let client = Client::try_default()
+        .await
+        .context("failed to create kubernetes client")?;
+    let store = (!args.dry_run).then(|| ConfigMapStore::new(client.clone(), args.configmap_name));
+    let generator = SidecarGenerator::new(
+        client,
+        SidecarOptions {
+            base_hosts: args.base_hosts,
+            name_prefix: args.name_prefix,
+            dry_run: args.dry_run,
+        },
+    );
+    let collector = Arc::new(Collector::new(CollectorOptions {
+        max_edges: args.max_edges,
+        ..CollectorOptions::default()
+    }));
+
+    if let Some(input) = args.input {
+        let reader: Box<dyn BufRead> = if input.as_os_str() == "-" {
+            Box::new(io::stdin().lock())
+        } else {
+            let file = File::open(&input)
+                .with_context(|| format!("failed to open {}", input.display()))?;
+            Box::new(BufReader::new(file))
+        };
+        for line in reader.lines() {
+            let line = line.context("failed to read edge report")?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            let report: EdgeReport =
+                serde_json::from_str(&line).context("failed to parse edge report")?;
+            collector.record(report);
+        }
+        return collector.sync(&generator, store.as_ref()).await;
+    }
+
+    let listen = args.listen.expect("either --input or --listen is required");
+    let listener = tokio::net::TcpListener::bind(listen)
+        .await
+        .with_context(|| format!("failed to listen on {}", listen))?;
+    info!("Accepting edge reports on {}{}", listen, args.path);
+    let app = server::router(&args.path, collector.clone());
+    tokio::select! {
+        result = axum::serve(listener, app) => result.context("edge report server failed"),
+        _ = collector.sync_forever(&generator, store.as_ref()) => Ok(()),
+        _ = tokio::signal::ctrl_c() => Ok(()),
+    }
+}
@@ -0,0 +1,292 @@
+use std::{
+    collections::{btree_map::Entry, BTreeMap, HashMap},
+    fmt::Write,
+    time::Duration,
+};
+
+use anyhow::{Context, Result};
+use dependency_edge::{Downstream, Upstream};
+use k8s_openapi::api::core::v1::ConfigMap;
+use serde::Deserialize;
+
+use crate::{
+    edge::{DependencyEdge, EdgeReport, Identity, ReportedEdge},
+    store::{self, EdgeStats},
+};
+
+/// A line of a JSON lines file: either a whole report, as read by the
+/// controller's `--input`, or a single edge.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum Line {
+    Report(EdgeReport),
+    Edge(Box<ReportedEdge>),
+}
+
+/// Parses edges from JSON lines, skipping blank lines.
+pub fn parse_json_lines(raw: &str) -> Result<Vec<ReportedEdge>> {
+    let mut edges = Vec::new();
+    for (number, line) in raw.lines().enumerate() {
+        if line.trim().is_empty() {
+            continue;
+        }
+        match serde_json::from_str(line)
+            .with_context(|| format!("failed to parse line {}", number + 1))?
+        {
+            Line::Report(report) => edges.extend(report.edges),
+            Line::Edge(edge) => edges.push(*edge),
+        }
+    }
+    Ok(edges)
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum ConfigMaps {
+    List { items: Vec<ConfigMap> },
+    One(Box<ConfigMap>),
+}
+
+/// Parses edges from the ConfigMaps the controller stores them in, as
+/// printed by `kubectl get configmap -o yaml`, singly or as a list. What
+/// the ConfigMaps do not keep, such as principals, is left unknown.
+pub fn parse_configmaps(raw: &str) -> Result<Vec<ReportedEdge>> {
+    let config_maps = match serde_yaml::from_str(raw).context("failed to parse configmaps")? {
+        ConfigMaps::List { items } => items,
+        ConfigMaps::One(config_map) => vec![*config_map],
+    };
+    let mut edges = Vec::new();
+    for config_map in config_maps {
+        let namespace = config_map
+            .metadata
+            .namespace
+            .context("configmap without a namespace")?;
+        for (service_account, upstreams) in store::parse(&config_map.data.unwrap_or_default()) {
+            for (cluster, stats) in upstreams {
+                edges.push(stats.report(DependencyEdge {
+                    downstream: Downstream {
+                        namespace: Some(namespace.clone()),
+                        service_account: Some(service_account.clone()),
+                        ..Downstream::default()
+                    },
+                    upstream: Upstream::from_cluster(&cluster),
+                    ..DependencyEdge::default()
+                }));
+            }
+        }
+    }
+    Ok(edges)
+}
+
+/// Fetches the edges a controller has collected, from the report path it
+/// listens on. Only plain http is supported, as served by the controller.
+pub async fn fetch(url: &str) -> Result<Vec<ReportedEdge>> {
+    let report: EdgeReport = reqwest::get(url)
+        .await
+        .and_then(reqwest::Response::error_for_status)
+        .with_context(|| format!("failed to fetch edges from {}", url))?
+        .json()
+        .await
+        .with_context(|| format!("failed to parse edges from {}", url))?;
+    Ok(report.edges)
+}
+
+/// What copies of an edge from different sources have in common.
+#[derive(PartialEq, Eq, PartialOrd, Ord)]
+enum EdgeKey {
+    /// The downstream workload and upstream cluster, the only parts of an
+    /// edge ConfigMaps keep.
+    Workload(Identity, String),
+    /// Edges without either are only merged with identical copies.
+    Other(Box<DependencyEdge>),
+}
+
+impl EdgeKey {
+    fn of(edge: &DependencyEdge) -> Self {
+        match (Identity::of(edge), edge.upstream.cluster.clone()) {
+            (Some(identity), Some(cluster)) => EdgeKey::Workload(identity, cluster),
+            _ => EdgeKey::Other(Box::new(edge.clone())),
+        }
+    }
+}
+
+/// Merges copies of edges seen more than once, e.g. in several sources.
+/// Sources hold totals of the same requests, so the highest counts are kept
+/// rather than summed. The copy kept is the most detailed one, i.e. with a
+/// principal, then the last seen.
+pub fn dedup(edges: Vec<ReportedEdge>) -> Vec<ReportedEdge> {
+    let mut merged = BTreeMap::<EdgeKey, (DependencyEdge, EdgeStats)>::new();
+    for edge in edges {
+        let stats = EdgeStats::from(&edge);
+        match merged.entry(EdgeKey::of(&edge.edge)) {
+            Entry::Vacant(entry) => {
+                entry.insert((edge.edge, stats));
+            }
+            Entry::Occupied(mut entry) => {
+                let (known, known_stats) = entry.get_mut();
+                let detail = |edge: &DependencyEdge, stats: &EdgeStats| {
+                    (edge.downstream.principal.is_some(), stats.last_seen)
+                };
+                if detail(&edge.edge, &stats) > detail(known, known_stats) {
+                    *known = edge.edge;
+                }
+                known_stats.merge_copy(&stats);
+            }
+        }
+    }
+    merged
+        .into_values()
+        .map(|(edge, stats)| stats.report(edge))
+        .collect()
+}
+
+/// Whether `target` names the upstream of `edge`: its cluster, host,
+/// service, or `<service>.<namespace>`.
+pub fn calls(edge: &DependencyEdge, target: &str) -> bool {
+    let upstream = &edge.upstream;
+    [&upstream.cluster, &upstream.host, &upstream.service]
+        .into_iter()
+        .any(|name| name.as_deref() == Some(target))
+        || upstream
+            .service
+            .as_ref()
+            .zip(upstream.namespace.as_ref())
+            .is_some_and(|(service, namespace)| format!("{}.{}", service, namespace) == target)
+}
+
+/// Whether the downstream of `edge` runs in `namespace`.
+pub fn in_namespace(edge: &DependencyEdge, namespace: &str) -> bool {
+    edge.downstream.namespace.as_deref() == Some(namespace)
+}
+
+/// Whether `edge` was last seen longer than `age` before `now`, in seconds
+/// since the Unix epoch.
+pub fn stale(edge: &ReportedEdge, now: u64, age: Duration) -> bool {
+    edge.last_seen < now.saturating_sub(age.as_secs())
+}
+
+/// `<namespace>/<service account>` of the downstream, else its principal.
+pub fn downstream_label(edge: &DependencyEdge) -> String {
+    let downstream = &edge.downstream;
+    match (&downstream.namespace, &downstream.service_account) {
+        (Some(namespace), Some(service_account)) => {
+            format!("{}/{}", namespace, service_account)
+        }
+        _ => downstream
+            .principal
+            .clone()
+            .unwrap_or_else(|| "?".to_string()),
+    }
+}
+
+/// Host of the upstream, else its cluster.
+pub fn upstream_label(edge: &DependencyEdge) -> String {
+    let upstream = &edge.upstream;
+    upstream
+        .host
+        .clone()
+        .or_else(|| upstream.cluster.clone())
+        .unwrap_or_else(|| "?".to_string())
+}
+
+/// Aligned columns of downstream, upstream, last seen, requests and errors.
+pub fn render_table(edges: &[ReportedEdge]) -> String {
+    let mut rows = vec![[
+        "DOWNSTREAM".to_string(),
+        "UPSTREAM".to_string(),
+        "LAST SEEN".to_string(),
+        "REQUESTS".to_string(),
+        "ERRORS".to_string(),
+    ]];
+    let count = |count: Option<u64>| count.map_or_else(|| "-".to_string(), |n| n.to_string());
+    rows.extend(edges.iter().map(|edge| {
+        [
+            downstream_label(&edge.edge),
+            upstream_label(&edge.edge),
+            edge.last_seen.to_string(),
+            count(edge.requests),
+            count(edge.errors),
+        ]
+    }));
+    let mut widths = [0; 5];
+    for row in &rows {
+        for (width, cell) in widths.iter_mut().zip(row) {
+            *width = (*width).max(cell.len());
+        }
+    }
+    let mut table = String::new();
+    for row in &rows {
+        let line = row
+            .iter()
+            .zip(widths)
+            .map(|(cell, width)| format!("{:width$}", cell, width = width))
+            .collect::<Vec<_>>()
+            .join("  ");
+        table.push_str(line.trim_end());
+        table.push('\n');
+    }
+    table
+}
+
+pub fn render_json(edges: &[ReportedEdge]) -> Result<String> {
+    let report = EdgeReport {
+        edges: edges.to_vec(),
+    };
+    Ok(serde_json::to_string_pretty(&report)? + "\n")
+}
+
+/// Pairs of downstream and upstream labels, without repeats, as graphs show
+/// edges between workloads rather than per request.
+fn links(edges: &[ReportedEdge]) -> Vec<(String, String)> {
+    let mut links: Vec<_> = edges
+        .iter()
+        .map(|edge| (downstream_label(&edge.edge), upstream_label(&edge.edge)))
+        .collect();
+    links.sort();
+    links.dedup();
+    links
+}
+
+/// `id` as a DOT quoted string. Backslashes are escaped along with quotes,
+/// as Graphviz reads escapes such as `\n` in the labels IDs stand for.
+fn dot_quote(id: &str) -> String {
+    format!("\"{}\"", id.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+/// A Graphviz digraph.
+pub fn render_dot(edges: &[ReportedEdge]) -> String {
+    let mut dot = "digraph dependencies {\n".to_string();
+    for (downstream, upstream) in links(edges) {
+        let _ = writeln!(
+            dot,
+            "  {} -> {};",
+            dot_quote(&downstream),
+            dot_quote(&upstream)
+        );
+    }
+    dot.push_str("}\n");
+    dot
+}
+
+/// A Mermaid flowchart. Nodes get generated IDs, as labels may contain
+/// characters Mermaid does not allow in them.
+pub fn render_mermaid(edges: &[ReportedEdge]) -> String {
+    let mut ids = HashMap::new();
+    let mut mermaid = "graph LR\n".to_string();
+    for (downstream, upstream) in links(edges) {
+        let mut node = |label: String| {
+            let next = ids.len();
+            match ids.get(&label) {
+                Some(id) => format!("n{}", id),
+                None => {
+                    ids.insert(label.clone(), next);
+                    format!("n{}[\"{}\"]", next, label.replace('"', "#quot;"))
+                }
+            }
+        };
+        let downstream = node(downstream);
+        let upstream = node(upstream);
+        let _ = writeln!(mermaid, "  {} --> {}", downstream, upstream);
+    }
+    mermaid
+}
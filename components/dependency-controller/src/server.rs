@@ -0,0 +1,226 @@
+use std::{
+    collections::BTreeMap,
+    mem,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use anyhow::Result;
+use axum::{extract::State, http::StatusCode, routing::post, Json, Router};
+use log::{error, trace, warn};
+use tokio::sync::Notify;
+
+use crate::{
+    edge::{Dependency, DependencyEdge, EdgeReport, Identity, ReportedEdge},
+    graph::DependencyGraph,
+    sidecar::SidecarGenerator,
+    store::{self, ConfigMapStore, EdgeStats},
+};
+
+#[derive(Default)]
+struct CollectorState {
+    graph: DependencyGraph,
+    /// Whether the graph grew since sidecars were last reconciled.
+    graph_changed: bool,
+    /// Edges not yet merged into the store.
+    unstored: Vec<ReportedEdge>,
+    /// Edges recorded since startup, for queries, up to
+    /// [`CollectorOptions::max_edges`].
+    edges: BTreeMap<DependencyEdge, EdgeStats>,
+}
+
+#[derive(Debug, Clone)]
+pub struct CollectorOptions {
+    /// Edges kept for queries. The least recently seen are dropped beyond
+    /// it; the store keeps them all.
+    pub max_edges: usize,
+    /// Delay before retrying a sync that left work undone, doubled on each
+    /// such sync in a row.
+    pub retry_delay: Duration,
+    /// Longest delay between retries.
+    pub max_retry_delay: Duration,
+}
+
+impl Default for CollectorOptions {
+    fn default() -> Self {
+        Self {
+            max_edges: 10_000,
+            retry_delay: Duration::from_secs(1),
+            max_retry_delay: Duration::from_secs(60),
+        }
+    }
+}
+
+/// Accumulates reported edges and signals when there is something to sync.
+pub struct Collector {
+    state: Mutex<CollectorState>,
+    changed: Notify,
+    options: CollectorOptions,
+}
+
+impl Default for Collector {
+    fn default() -> Self {
+        Self::new(CollectorOptions::default())
+    }
+}
+
+impl Collector {
+    pub fn new(options: CollectorOptions) -> Self {
+        Self {
+            state: Mutex::default(),
+            changed: Notify::new(),
+            options,
+        }
+    }
+
+    /// Merges a report into the graph, returning whether anything new was
+    /// learned.
+    pub fn record(&self, report: EdgeReport) -> bool {
+        let mut state = self.state.lock().expect("collector lock poisoned");
+        let mut changed = false;
+        for edge in report.edges.iter() {
+            match Dependency::of(&edge.edge) {
+                Some(dependency) => changed |= state.graph.insert(dependency),
+                None => trace!("Ignoring edge {} for sidecars", edge.edge.legacy()),
+            }
+        }
+        state.graph_changed |= changed;
+        for edge in report.edges.iter() {
+            let stats = EdgeStats::from(edge);
+            state
+                .edges
+                .entry(edge.edge.clone())
+                .and_modify(|existing| existing.merge(&stats))
+                .or_insert(stats);
+        }
+        let excess = state.edges.len().saturating_sub(self.options.max_edges);
+        if excess > 0 {
+            let mut by_age: Vec<_> = state.edges.iter().collect();
+            by_age.sort_by_key(|(_, stats)| stats.last_seen);
+            let evicted: Vec<_> = by_age[..excess]
+                .iter()
+                .map(|(edge, _)| (*edge).clone())
+                .collect();
+            for edge in evicted {
+                state.edges.remove(&edge);
+            }
+        }
+        state.unstored.extend(report.edges);
+        self.changed.notify_one();
+        changed
+    }
+
+    pub fn graph(&self) -> DependencyGraph {
+        self.state
+            .lock()
+            .expect("collector lock poisoned")
+            .graph
+            .clone()
+    }
+
+    /// Every edge recorded so far, with the stats of its reports merged.
+    pub fn edges(&self) -> Vec<ReportedEdge> {
+        let state = self.state.lock().expect("collector lock poisoned");
+        state
+            .edges
+            .iter()
+            .map(|(edge, stats)| stats.report(edge.clone()))
+            .collect()
+    }
+
+    /// Merges edges recorded since the last sync into the store, and
+    /// reconciles sidecars if the graph grew. Edges that failed to be stored
+    /// are kept for the next sync.
+    pub async fn sync(
+        &self,
+        generator: &SidecarGenerator,
+        store: Option<&ConfigMapStore>,
+    ) -> Result<()> {
+        let (unstored, graph) = {
+            let mut state = self.state.lock().expect("collector lock poisoned");
+            let graph = mem::take(&mut state.graph_changed).then(|| state.graph.clone());
+            (mem::take(&mut state.unstored), graph)
+        };
+
+        if let Some(store) = store {
+            let mut failed = Vec::new();
+            for (namespace, update) in store::group_by_namespace(&unstored) {
+                if let Err(err) = store.merge(&namespace, &update).await {
+                    error!("{:#}", err);
+                    failed.push(namespace);
+                }
+            }
+            if !failed.is_empty() {
+                let mut state = self.state.lock().expect("collector lock poisoned");
+                state.unstored.extend(unstored.into_iter().filter(|edge| {
+                    Identity::of(&edge.edge)
+                        .is_some_and(|identity| failed.contains(&identity.namespace))
+                }));
+            }
+        }
+
+        if let Some(graph) = graph {
+            if let Err(err) = generator.reconcile(&graph).await {
+                self.state
+                    .lock()
+                    .expect("collector lock poisoned")
+                    .graph_changed = true;
+                return Err(err);
+            }
+        }
+        Ok(())
+    }
+
+    /// Whether a sync left edges unstored or sidecars unreconciled.
+    fn pending(&self) -> bool {
+        let state = self.state.lock().expect("collector lock poisoned");
+        state.graph_changed || !state.unstored.is_empty()
+    }
+
+    /// Syncs every time something is recorded, and retries with backoff
+    /// while syncs leave work undone. Never returns.
+    pub async fn sync_forever(&self, generator: &SidecarGenerator, store: Option<&ConfigMapStore>) {
+        let mut retry_delay = None;
+        loop {
+            match retry_delay {
+                None => self.changed.notified().await,
+                Some(delay) => tokio::select! {
+                    _ = self.changed.notified() => {}
+                    _ = tokio::time::sleep(delay) => {}
+                },
+            }
+            if let Err(err) = self.sync(generator, store).await {
+                error!("Failed to sync learned dependencies: {:#}", err);
+            }
+            retry_delay = self.pending().then(|| match retry_delay {
+                Some(delay) => (delay * 2).min(self.options.max_retry_delay),
+                None => self.options.retry_delay,
+            });
+            if let Some(delay) = retry_delay {
+                warn!("Retrying sync of learned dependencies in {:?}", delay);
+            }
+        }
+    }
+}
+
+/// Routes edge reports POSTed to `path` into the collector, and answers GETs
+/// of `path` with every edge recorded so far.
+pub fn router(path: &str, collector: Arc<Collector>) -> Router {
+    Router::new()
+        .route(path, post(receive).get(list))
+        .with_state(collector)
+}
+
+async fn receive(
+    State(collector): State<Arc<Collector>>,
+    Json(report): Json<EdgeReport>,
+) -> StatusCode {
+    collector.record(report);
+    StatusCode::NO_CONTENT
+}
+
+async fn list(State(collector): State<Arc<Collector>>) -> Json<EdgeReport> {
+    Json(EdgeReport {
+        edges: collector.edges(),
+    })
+}
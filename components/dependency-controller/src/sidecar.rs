@@ -0,0 +1,274 @@
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fmt,
+};
+
+use anyhow::{Context, Result};
+use k8s_openapi::api::core::v1::Pod;
+use kube::{
+    api::{
+        ApiResource, DynamicObject, GroupVersionKind, ListParams, ObjectMeta, Patch, PatchParams,
+    },
+    Api, Client,
+};
+use log::{error, info, warn};
+use serde_json::json;
+
+use crate::{edge::Identity, graph::DependencyGraph};
+
+pub const FIELD_MANAGER: &str = "dependency-controller";
+
+/// Pod labels that differ between replicas of the same workload.
+const VOLATILE_LABELS: &[&str] = &["pod-template-hash", "controller-revision-hash"];
+
+fn sidecar_resource() -> ApiResource {
+    ApiResource::from_gvk_with_plural(
+        &GroupVersionKind::gvk("networking.istio.io", "v1beta1", "Sidecar"),
+        "sidecars",
+    )
+}
+
+#[derive(Debug, Clone)]
+pub struct SidecarOptions {
+    /// Egress hosts every generated `Sidecar` imports besides the learned
+    /// ones, e.g. the control plane and the fallback route.
+    pub base_hosts: Vec<String>,
+    /// Prefix of generated `Sidecar` names, followed by the service account.
+    pub name_prefix: String,
+    /// Print the generated resources as YAML instead of applying them.
+    pub dry_run: bool,
+}
+
+impl Default for SidecarOptions {
+    fn default() -> Self {
+        Self {
+            base_hosts: vec!["./*".to_string(), "istio-system/*".to_string()],
+            name_prefix: "learned-".to_string(),
+            dry_run: false,
+        }
+    }
+}
+
+/// Why an identity gets no `Sidecar`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Skip {
+    /// No running pod uses the service account.
+    NoPods,
+    /// The pods of the service account have no labels in common to select
+    /// them by.
+    NoCommonLabels,
+    /// The labels the pods of the service account have in common also
+    /// select pods of these other service accounts, whose egress the
+    /// `Sidecar` would cut off.
+    SelectsOthers(BTreeSet<String>),
+}
+
+impl fmt::Display for Skip {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Skip::NoPods => write!(f, "no pods found"),
+            Skip::NoCommonLabels => write!(f, "its pods have no labels in common"),
+            Skip::SelectsOthers(others) => write!(
+                f,
+                "its pods' labels also select pods of service account {}",
+                others.iter().cloned().collect::<Vec<_>>().join(", ")
+            ),
+        }
+    }
+}
+
+/// Generates one `Sidecar` per downstream identity.
+pub struct SidecarGenerator {
+    client: Client,
+    options: SidecarOptions,
+}
+
+impl SidecarGenerator {
+    pub fn new(client: Client, options: SidecarOptions) -> Self {
+        Self { client, options }
+    }
+
+    /// Renders the `Sidecar` for an identity. Returns `None` if the pods of
+    /// the identity's service account cannot be selected on their own, e.g.
+    /// as there are none, saying why in the log and, when running dry, in
+    /// the output.
+    pub async fn generate(
+        &self,
+        identity: &Identity,
+        hosts: &BTreeSet<String>,
+    ) -> Result<Option<DynamicObject>> {
+        let labels = match self.workload_labels(identity).await? {
+            Ok(labels) => labels,
+            Err(skip) => {
+                warn!(
+                    "Skipping service account {}/{}: {}",
+                    identity.namespace, identity.service_account, skip
+                );
+                if self.options.dry_run {
+                    println!(
+                        "# skipped service account {}/{}: {}",
+                        identity.namespace, identity.service_account, skip
+                    );
+                }
+                return Ok(None);
+            }
+        };
+
+        let mut egress_hosts = self.options.base_hosts.clone();
+        egress_hosts.extend(hosts.iter().map(|host| egress_host(host)));
+
+        let resource = sidecar_resource();
+        let mut sidecar = DynamicObject::new(
+            &format!("{}{}", self.options.name_prefix, identity.service_account),
+            &resource,
+        )
+        .within(&identity.namespace)
+        .data(json!({
+            "spec": {
+                "workloadSelector": {
+                    "labels": labels,
+                },
+                "egress": [
+                    {
+                        "hosts": egress_hosts,
+                    },
+                ],
+            },
+        }));
+        sidecar.metadata.labels = Some(BTreeMap::from([(
+            "app.kubernetes.io/managed-by".to_string(),
+            FIELD_MANAGER.to_string(),
+        )]));
+        Ok(Some(sidecar))
+    }
+
+    /// Generates and applies the `Sidecar` of every identity in the graph,
+    /// returning those applied. Failing identities are logged and do not
+    /// hold up the others; the error lists them.
+    pub async fn reconcile(&self, graph: &DependencyGraph) -> Result<Vec<DynamicObject>> {
+        let mut sidecars = Vec::new();
+        let mut failed = Vec::new();
+        for (identity, hosts) in graph.iter() {
+            let applied = match self.generate(identity, hosts).await {
+                Ok(Some(sidecar)) => self.apply(&sidecar).await.map(|()| Some(sidecar)),
+                Ok(None) => Ok(None),
+                Err(err) => Err(err),
+            };
+            match applied {
+                Ok(sidecar) => sidecars.extend(sidecar),
+                Err(err) => {
+                    error!("{:#}", err);
+                    failed.push(format!(
+                        "{}/{}",
+                        identity.namespace, identity.service_account
+                    ));
+                }
+            }
+        }
+        if !failed.is_empty() {
+            anyhow::bail!(
+                "failed to reconcile sidecars of service accounts {}",
+                failed.join(", ")
+            );
+        }
+        Ok(sidecars)
+    }
+
+    /// Server-side applies the `Sidecar`, or prints it when running dry.
+    pub async fn apply(&self, sidecar: &DynamicObject) -> Result<()> {
+        if self.options.dry_run {
+            print!("---\n{}", to_yaml(sidecar)?);
+            return Ok(());
+        }
+        let ObjectMeta {
+            name: Some(name),
+            namespace: Some(namespace),
+            ..
+        } = &sidecar.metadata
+        else {
+            anyhow::bail!("sidecar is missing its name or namespace");
+        };
+        let api: Api<DynamicObject> =
+            Api::namespaced_with(self.client.clone(), namespace, &sidecar_resource());
+        api.patch(
+            name,
+            &PatchParams::apply(FIELD_MANAGER).force(),
+            &Patch::Apply(sidecar),
+        )
+        .await
+        .with_context(|| format!("failed to apply sidecar {}/{}", namespace, name))?;
+        info!("Applied sidecar {}/{}", namespace, name);
+        Ok(())
+    }
+
+    /// Labels shared by all pods running as the identity's service account,
+    /// provided they select no pods of other service accounts.
+    async fn workload_labels(
+        &self,
+        identity: &Identity,
+    ) -> Result<Result<BTreeMap<String, String>, Skip>> {
+        let pods: Api<Pod> = Api::namespaced(self.client.clone(), &identity.namespace);
+        let pods = pods
+            .list(&ListParams::default())
+            .await
+            .with_context(|| format!("failed to list pods in {}", identity.namespace))?;
+
+        let (own, others): (Vec<_>, Vec<_>) = pods
+            .items
+            .iter()
+            .partition(|pod| service_account(pod) == identity.service_account);
+        let mut common: Option<BTreeMap<String, String>> = None;
+        for pod in own {
+            let labels = pod.metadata.labels.clone().unwrap_or_default();
+            common = Some(match common {
+                None => labels,
+                Some(common) => common
+                    .into_iter()
+                    .filter(|(key, value)| labels.get(key) == Some(value))
+                    .collect(),
+            });
+        }
+        let Some(mut labels) = common else {
+            return Ok(Err(Skip::NoPods));
+        };
+        labels.retain(|key, _| !VOLATILE_LABELS.contains(&key.as_str()));
+        if labels.is_empty() {
+            return Ok(Err(Skip::NoCommonLabels));
+        }
+        let selected: BTreeSet<String> = others
+            .into_iter()
+            .filter(|pod| {
+                let pod_labels = pod.metadata.labels.as_ref();
+                labels.iter().all(|(key, value)| {
+                    pod_labels.and_then(|pod_labels| pod_labels.get(key)) == Some(value)
+                })
+            })
+            .map(|pod| service_account(pod).to_string())
+            .collect();
+        if !selected.is_empty() {
+            return Ok(Err(Skip::SelectsOthers(selected)));
+        }
+        Ok(Ok(labels))
+    }
+}
+
+/// The service account a pod runs as.
+fn service_account(pod: &Pod) -> &str {
+    pod.spec
+        .as_ref()
+        .and_then(|spec| spec.service_account_name.as_deref())
+        .unwrap_or("default")
+}
+
+/// Qualifies a host with the namespace it is exported from, as `Sidecar`
+/// egress hosts are written as `<namespace>/<host>`.
+fn egress_host(host: &str) -> String {
+    match host.split('.').collect::<Vec<_>>()[..] {
+        [_service, namespace, "svc", ..] => format!("{}/{}", namespace, host),
+        _ => format!("*/{}", host),
+    }
+}
+
+pub fn to_yaml(sidecar: &DynamicObject) -> Result<String> {
+    serde_yaml::to_string(sidecar).context("failed to serialize sidecar")
+}
@@ -0,0 +1,220 @@
+use std::collections::BTreeMap;
+
+use anyhow::{Context, Result};
+use k8s_openapi::api::core::v1::ConfigMap;
+use kube::{
+    api::{ObjectMeta, PostParams},
+    Api, Client,
+};
+use log::{info, warn};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    edge::{DependencyEdge, Identity, ReportedEdge},
+    sidecar::FIELD_MANAGER,
+};
+
+pub const DEFAULT_CONFIGMAP_NAME: &str = "learned-dependencies";
+
+/// Attempts at a read-modify-write before giving up on conflicting writers.
+const MAX_CONFLICT_RETRIES: usize = 8;
+
+/// What is known about a single upstream of a service account.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct EdgeStats {
+    pub first_seen: u64,
+    pub last_seen: u64,
+    #[serde(default)]
+    pub requests: u64,
+    #[serde(default)]
+    pub errors: u64,
+}
+
+impl EdgeStats {
+    pub fn merge(&mut self, other: &EdgeStats) {
+        self.first_seen = self.first_seen.min(other.first_seen);
+        self.last_seen = self.last_seen.max(other.last_seen);
+        self.requests += other.requests;
+        self.errors += other.errors;
+    }
+
+    /// Merges another copy of the same totals, e.g. read from another
+    /// source. Counts are not added up, as that would count requests both
+    /// copies saw twice; the highest is kept instead.
+    pub fn merge_copy(&mut self, other: &EdgeStats) {
+        self.first_seen = self.first_seen.min(other.first_seen);
+        self.last_seen = self.last_seen.max(other.last_seen);
+        self.requests = self.requests.max(other.requests);
+        self.errors = self.errors.max(other.errors);
+    }
+
+    /// The edge as reported with these stats.
+    pub fn report(self, edge: DependencyEdge) -> ReportedEdge {
+        ReportedEdge {
+            edge,
+            first_seen: self.first_seen,
+            last_seen: self.last_seen,
+            requests: Some(self.requests),
+            errors: Some(self.errors),
+            outcomes: None,
+        }
+    }
+}
+
+impl From<&ReportedEdge> for EdgeStats {
+    fn from(edge: &ReportedEdge) -> Self {
+        Self {
+            first_seen: edge.first_seen,
+            last_seen: edge.last_seen,
+            requests: edge.requests.unwrap_or_default(),
+            errors: edge.errors.unwrap_or_default(),
+        }
+    }
+}
+
+/// Upstream cluster to stats, keyed by service account.
+///
+/// This is the layout of the ConfigMap's data: one key per service account,
+/// whose value is a YAML mapping sorted by upstream cluster, so that a change
+/// to one edge shows up as a change to a handful of lines.
+pub type NamespaceEdges = BTreeMap<String, BTreeMap<String, EdgeStats>>;
+
+/// Groups reported edges by downstream namespace. Edges without a SPIFFE
+/// downstream or an upstream cluster are dropped, as they belong to no
+/// namespace or have nothing to key on.
+pub fn group_by_namespace<'a>(
+    edges: impl IntoIterator<Item = &'a ReportedEdge>,
+) -> BTreeMap<String, NamespaceEdges> {
+    let mut grouped = BTreeMap::<String, NamespaceEdges>::new();
+    for edge in edges {
+        let (Some(identity), Some(upstream)) = (
+            Identity::of(&edge.edge),
+            edge.edge.upstream.cluster.as_deref(),
+        ) else {
+            continue;
+        };
+        let stats = EdgeStats::from(edge);
+        grouped
+            .entry(identity.namespace)
+            .or_default()
+            .entry(identity.service_account)
+            .or_default()
+            .entry(upstream.to_string())
+            .and_modify(|existing| existing.merge(&stats))
+            .or_insert(stats);
+    }
+    grouped
+}
+
+/// Merges `update` into `edges`.
+pub fn merge(edges: &mut NamespaceEdges, update: &NamespaceEdges) {
+    for (service_account, upstreams) in update {
+        let known = edges.entry(service_account.clone()).or_default();
+        for (upstream, stats) in upstreams {
+            known
+                .entry(upstream.clone())
+                .and_modify(|existing| existing.merge(stats))
+                .or_insert(*stats);
+        }
+    }
+}
+
+/// Parses ConfigMap data. Keys that fail to parse are skipped with a warning
+/// and will be overwritten by the next write.
+pub fn parse(data: &BTreeMap<String, String>) -> NamespaceEdges {
+    data.iter()
+        .filter_map(|(service_account, raw)| {
+            serde_yaml::from_str(raw)
+                .inspect_err(|err| warn!("Ignoring malformed entry {}: {}", service_account, err))
+                .ok()
+                .map(|upstreams| (service_account.clone(), upstreams))
+        })
+        .collect()
+}
+
+pub fn render(edges: &NamespaceEdges) -> Result<BTreeMap<String, String>> {
+    edges
+        .iter()
+        .map(|(service_account, upstreams)| {
+            let raw = serde_yaml::to_string(upstreams)
+                .with_context(|| format!("failed to serialize edges of {}", service_account))?;
+            Ok((service_account.clone(), raw))
+        })
+        .collect()
+}
+
+/// Persists the dependency graph as one ConfigMap per downstream namespace.
+pub struct ConfigMapStore {
+    client: Client,
+    name: String,
+}
+
+impl ConfigMapStore {
+    pub fn new(client: Client, name: impl Into<String>) -> Self {
+        Self {
+            client,
+            name: name.into(),
+        }
+    }
+
+    /// Merges edges into the namespace's ConfigMap, creating it if needed.
+    /// Writes are conditional on the `resourceVersion` that was read, and are
+    /// retried from a fresh read when another writer got there first.
+    pub async fn merge(&self, namespace: &str, update: &NamespaceEdges) -> Result<()> {
+        let api: Api<ConfigMap> = Api::namespaced(self.client.clone(), namespace);
+        for _ in 0..MAX_CONFLICT_RETRIES {
+            let existing = api
+                .get_opt(&self.name)
+                .await
+                .with_context(|| format!("failed to get configmap {}/{}", namespace, self.name))?;
+            let result = match existing {
+                Some(mut config_map) => {
+                    let mut edges = parse(&config_map.data.take().unwrap_or_default());
+                    merge(&mut edges, update);
+                    config_map.data = Some(render(&edges)?);
+                    api.replace(&self.name, &PostParams::default(), &config_map)
+                        .await
+                }
+                None => {
+                    let config_map = ConfigMap {
+                        metadata: ObjectMeta {
+                            name: Some(self.name.clone()),
+                            namespace: Some(namespace.to_string()),
+                            labels: Some(BTreeMap::from([(
+                                "app.kubernetes.io/managed-by".to_string(),
+                                FIELD_MANAGER.to_string(),
+                            )])),
+                            ..ObjectMeta::default()
+                        },
+                        data: Some(render(update)?),
+                        ..ConfigMap::default()
+                    };
+                    api.create(&PostParams::default(), &config_map).await
+                }
+            };
+            match result {
+                Ok(_) => {
+                    info!("Updated configmap {}/{}", namespace, self.name);
+                    return Ok(());
+                }
+                Err(kube::Error::Api(status)) if status.code == 409 => {
+                    warn!(
+                        "Conflict updating configmap {}/{}; retrying",
+                        namespace, self.name
+                    );
+                }
+                Err(err) => {
+                    return Err(err).with_context(|| {
+                        format!("failed to write configmap {}/{}", namespace, self.name)
+                    });
+                }
+            }
+        }
+        anyhow::bail!(
+            "gave up updating configmap {}/{} after {} conflicts",
+            namespace,
+            self.name,
+            MAX_CONFLICT_RETRIES
+        )
+    }
+}
@@ -0,0 +1,181 @@
+use std::{sync::Arc, time::Duration};
+
+use dependency_controller::{
+    edge::EdgeReport,
+    query,
+    server::{Collector, CollectorOptions},
+};
+
+const JSONL: &str = r#"
+{"edges": [{"edge": {"downstream": {"principal": "spiffe://cluster.local/ns/client/sa/default", "namespace": "client", "service_account": "default"}, "upstream": {"cluster": "outbound|80||server.server.svc.cluster.local", "direction": "outbound", "port": 80, "host": "server.server.svc.cluster.local", "service": "server", "namespace": "server"}}, "first_seen": 100, "last_seen": 200, "requests": 3, "errors": 1}]}
+
+{"edge": {"downstream": {"principal": "spiffe://cluster.local/ns/client/sa/default", "namespace": "client", "service_account": "default"}, "upstream": {"cluster": "outbound|80||server.server.svc.cluster.local", "direction": "outbound", "port": 80, "host": "server.server.svc.cluster.local", "service": "server", "namespace": "server"}}, "first_seen": 50, "last_seen": 300, "requests": 2}
+{"edge": {"downstream": {"principal": "spiffe://cluster.local/ns/batch/sa/cron", "namespace": "batch", "service_account": "cron"}, "upstream": {"cluster": "outbound|443||api.example.com", "direction": "outbound", "port": 443, "host": "api.example.com"}}, "first_seen": 100, "last_seen": 100}
+"#;
+
+const CONFIGMAPS: &str = r#"
+apiVersion: v1
+kind: List
+items:
+- apiVersion: v1
+  kind: ConfigMap
+  metadata:
+    name: learned-dependencies
+    namespace: client
+  data:
+    default: |
+      outbound|80||server.server.svc.cluster.local:
+        first_seen: 100
+        last_seen: 200
+        requests: 3
+        errors: 1
+"#;
+
+#[test]
+fn merges_edges_from_json_lines() {
+    let edges = query::dedup(query::parse_json_lines(JSONL).unwrap());
+    assert_eq!(edges.len(), 2);
+    let server = edges
+        .iter()
+        .find(|edge| query::calls(&edge.edge, "server.server"))
+        .unwrap();
+    assert_eq!((server.first_seen, server.last_seen), (50, 300));
+    assert_eq!((server.requests, server.errors), (Some(3), Some(1)));
+}
+
+#[test]
+fn merges_configmap_edges_with_collected_ones() {
+    let mut edges = query::parse_configmaps(CONFIGMAPS).unwrap();
+    edges.extend(query::parse_json_lines(JSONL).unwrap());
+    let edges = query::dedup(edges);
+    assert_eq!(edges.len(), 2);
+    let server = edges
+        .iter()
+        .find(|edge| query::calls(&edge.edge, "server"))
+        .unwrap();
+    assert_eq!(
+        server.edge.downstream.principal.as_deref(),
+        Some("spiffe://cluster.local/ns/client/sa/default")
+    );
+    assert_eq!((server.first_seen, server.last_seen), (50, 300));
+    assert_eq!((server.requests, server.errors), (Some(3), Some(1)));
+}
+
+#[test]
+fn reports_line_of_malformed_json() {
+    let err = query::parse_json_lines("{\"edges\": []}\nnot json").unwrap_err();
+    assert_eq!(err.to_string(), "failed to parse line 2");
+}
+
+#[test]
+fn reads_edges_from_configmaps() {
+    let edges = query::parse_configmaps(CONFIGMAPS).unwrap();
+    assert_eq!(edges.len(), 1);
+    assert_eq!(query::downstream_label(&edges[0].edge), "client/default");
+    assert_eq!(
+        query::upstream_label(&edges[0].edge),
+        "server.server.svc.cluster.local"
+    );
+    assert!(query::calls(&edges[0].edge, "server"));
+    assert_eq!(edges[0].requests, Some(3));
+}
+
+#[test]
+fn filters_edges() {
+    let edges = query::dedup(query::parse_json_lines(JSONL).unwrap());
+    let labels = |keep: &dyn Fn(&dependency_controller::edge::ReportedEdge) -> bool| {
+        edges
+            .iter()
+            .filter(|edge| keep(edge))
+            .map(|edge| query::upstream_label(&edge.edge))
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(
+        labels(&|edge| query::calls(&edge.edge, "api.example.com")),
+        ["api.example.com"]
+    );
+    assert_eq!(
+        labels(&|edge| query::calls(&edge.edge, "outbound|80||server.server.svc.cluster.local")),
+        ["server.server.svc.cluster.local"]
+    );
+    assert_eq!(
+        labels(&|edge| query::in_namespace(&edge.edge, "batch")),
+        ["api.example.com"]
+    );
+    let day = Duration::from_secs(86_400);
+    assert_eq!(
+        labels(&|edge| query::stale(edge, 86_400 + 250, day)),
+        ["api.example.com"]
+    );
+}
+
+#[test]
+fn renders_graphs() {
+    let edges = query::dedup(query::parse_json_lines(JSONL).unwrap());
+    assert_eq!(
+        query::render_dot(&edges),
+        "digraph dependencies {\n  \"batch/cron\" -> \"api.example.com\";\n  \"client/default\" -> \"server.server.svc.cluster.local\";\n}\n"
+    );
+    assert_eq!(
+        query::render_mermaid(&edges),
+        "graph LR\n  n0[\"batch/cron\"] --> n1[\"api.example.com\"]\n  n2[\"client/default\"] --> n3[\"server.server.svc.cluster.local\"]\n"
+    );
+    assert_eq!(
+        query::render_table(&edges),
+        "DOWNSTREAM      UPSTREAM                         LAST SEEN  REQUESTS  ERRORS\n\
+         batch/cron      api.example.com                  100        0         0\n\
+         client/default  server.server.svc.cluster.local  300        3         1\n"
+    );
+    let report: EdgeReport = serde_json::from_str(&query::render_json(&edges).unwrap()).unwrap();
+    assert_eq!(report.edges.len(), 2);
+}
+
+#[test]
+fn quotes_dot_ids() {
+    let edges = query::parse_json_lines(
+        r#"{"edge": {"downstream": {"principal": "spiffe://café/\"odd\\"}, "upstream": {"cluster": "c"}}, "first_seen": 1, "last_seen": 1}"#,
+    )
+    .unwrap();
+    assert_eq!(
+        query::render_dot(&edges),
+        r#"digraph dependencies {
+  "spiffe://café/\"odd\\" -> "c";
+}
+"#
+    );
+}
+
+#[tokio::test]
+async fn fetches_edges_from_collector() {
+    let collector = Arc::new(Collector::default());
+    collector.record(EdgeReport {
+        edges: query::parse_json_lines(JSONL).unwrap(),
+    });
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    let app = dependency_controller::server::router("/edges", collector);
+    tokio::spawn(async move { axum::serve(listener, app).await });
+
+    let edges = query::fetch(&format!("http://{}/edges", addr))
+        .await
+        .unwrap();
+    assert_eq!(edges.len(), 2);
+    assert!(edges
+        .iter()
+        .any(|edge| query::calls(&edge.edge, "server") && edge.requests == Some(5)));
+}
+
+#[test]
+fn keeps_most_recently_seen_edges_of_collector() {
+    let collector = Collector::new(CollectorOptions {
+        max_edges: 1,
+        ..CollectorOptions::default()
+    });
+    let edges = query::parse_json_lines(JSONL).unwrap();
+    let (old, recent): (Vec<_>, Vec<_>) = edges.into_iter().partition(|edge| edge.last_seen < 150);
+    collector.record(EdgeReport { edges: recent });
+    collector.record(EdgeReport { edges: old });
+    let edges = collector.edges();
+    assert_eq!(edges.len(), 1);
+    assert!(query::calls(&edges[0].edge, "server"));
+}
@@ -0,0 +1,264 @@
+use std::time::Duration;
+
+use dependency_controller::{
+    edge::EdgeReport,
+    server::{Collector, CollectorOptions},
+    sidecar::{self, SidecarGenerator, SidecarOptions},
+};
+use kube::{Client, Config};
+use serde_json::json;
+use wiremock::{
+    matchers::{method, path},
+    Mock, MockServer, ResponseTemplate,
+};
+
+const REPORT: &str = r#"{"edges": [
+    {"edge": {"downstream": {"principal": "spiffe://cluster.local/ns/client/sa/default", "trust_domain": "cluster.local", "namespace": "client", "service_account": "default"}, "upstream": {"cluster": "outbound|80||server.server.svc.cluster.local", "direction": "outbound", "port": 80, "host": "server.server.svc.cluster.local"}}, "first_seen": 1, "last_seen": 2},
+    {"edge": {"downstream": {"principal": "spiffe://cluster.local/ns/client/sa/default", "trust_domain": "cluster.local", "namespace": "client", "service_account": "default"}, "upstream": {"cluster": "outbound|443||api.example.com", "direction": "outbound", "port": 443, "host": "api.example.com"}}, "first_seen": 1, "last_seen": 2},
+    {"edge": {"downstream": {"principal": "spiffe://cluster.local/ns/client/sa/batch", "trust_domain": "cluster.local", "namespace": "client", "service_account": "batch"}, "upstream": {"cluster": "outbound|80||server.server.svc.cluster.local", "direction": "outbound", "port": 80, "host": "server.server.svc.cluster.local"}}, "first_seen": 1, "last_seen": 2},
+    {"edge": {"downstream": {}, "upstream": {"cluster": "outbound|80||server.server.svc.cluster.local", "direction": "outbound", "port": 80, "host": "server.server.svc.cluster.local"}}, "first_seen": 1, "last_seen": 2},
+    {"edge": {"downstream": {"principal": "spiffe://cluster.local/ns/client/sa/default", "trust_domain": "cluster.local", "namespace": "client", "service_account": "default"}, "upstream": {"cluster": "PassthroughCluster"}}, "first_seen": 1, "last_seen": 2}
+]}"#;
+
+fn pod(name: &str, service_account: &str, labels: serde_json::Value) -> serde_json::Value {
+    json!({
+        "metadata": {"name": name, "namespace": "client", "labels": labels},
+        "spec": {"containers": [], "serviceAccountName": service_account},
+    })
+}
+
+/// Serves the pods of the `client` namespace, as a real API server would.
+async fn fake_api_server_with(pods: Vec<serde_json::Value>) -> MockServer {
+    let server = MockServer::start().await;
+    Mock::given(method("GET"))
+        .and(path("/api/v1/namespaces/client/pods"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+            "apiVersion": "v1",
+            "kind": "PodList",
+            "metadata": {},
+            "items": pods,
+        })))
+        .mount(&server)
+        .await;
+    server
+}
+
+/// Two replicas of the `client` deployment, running as `default`.
+async fn fake_api_server() -> MockServer {
+    let labels = json!({"app": "client", "pod-template-hash": "7d4b9c"});
+    fake_api_server_with(vec![
+        pod("client-7d4b9c-abcde", "default", labels.clone()),
+        pod("client-7d4b9c-fghij", "default", labels),
+    ])
+    .await
+}
+
+fn generator(server: &MockServer, dry_run: bool) -> SidecarGenerator {
+    let config = Config::new(server.uri().parse().unwrap());
+    SidecarGenerator::new(
+        Client::try_from(config).unwrap(),
+        SidecarOptions {
+            dry_run,
+            ..SidecarOptions::default()
+        },
+    )
+}
+
+fn collector() -> Collector {
+    let collector = Collector::default();
+    collector.record(serde_json::from_str::<EdgeReport>(REPORT).unwrap());
+    collector
+}
+
+#[tokio::test]
+async fn dry_run_renders_sidecar_per_identity() {
+    let server = fake_api_server().await;
+
+    let sidecars = generator(&server, true)
+        .reconcile(&collector().graph())
+        .await
+        .unwrap();
+
+    // the batch service account has no pods to select
+    assert_eq!(sidecars.len(), 1);
+    assert_eq!(
+        sidecar::to_yaml(&sidecars[0]).unwrap(),
+        r#"apiVersion: networking.istio.io/v1beta1
+kind: Sidecar
+metadata:
+  labels:
+    app.kubernetes.io/managed-by: dependency-controller
+  name: learned-default
+  namespace: client
+spec:
+  egress:
+  - hosts:
+    - ./*
+    - istio-system/*
+    - '*/api.example.com'
+    - server/server.server.svc.cluster.local
+  workloadSelector:
+    labels:
+      app: client
+"#
+    );
+    let applied = server
+        .received_requests()
+        .await
+        .unwrap()
+        .into_iter()
+        .filter(|request| request.method.as_str() != "GET")
+        .count();
+    assert_eq!(applied, 0);
+}
+
+#[tokio::test]
+async fn applies_sidecar_to_api_server() {
+    let server = fake_api_server().await;
+    Mock::given(method("PATCH"))
+        .and(path(
+            "/apis/networking.istio.io/v1beta1/namespaces/client/sidecars/learned-default",
+        ))
+        .respond_with(|request: &wiremock::Request| {
+            ResponseTemplate::new(200).set_body_bytes(request.body.clone())
+        })
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    generator(&server, false)
+        .reconcile(&collector().graph())
+        .await
+        .unwrap();
+
+    let patch = server
+        .received_requests()
+        .await
+        .unwrap()
+        .into_iter()
+        .find(|request| request.method.as_str() == "PATCH")
+        .unwrap();
+    let query = patch.url.query().unwrap_or_default();
+    assert!(query.contains("fieldManager=dependency-controller"));
+    assert!(query.contains("force=true"));
+    let body: serde_json::Value = serde_json::from_slice(&patch.body).unwrap();
+    assert_eq!(
+        body["spec"]["egress"][0]["hosts"],
+        json!([
+            "./*",
+            "istio-system/*",
+            "*/api.example.com",
+            "server/server.server.svc.cluster.local",
+        ])
+    );
+}
+
+#[tokio::test]
+async fn skips_selector_matching_other_service_accounts() {
+    let server = fake_api_server_with(vec![
+        pod("client-7d4b9c-abcde", "default", json!({"app": "client"})),
+        pod(
+            "client-batch-xyz",
+            "batch",
+            json!({"app": "client", "job": "batch"}),
+        ),
+    ])
+    .await;
+
+    let sidecars = generator(&server, true)
+        .reconcile(&collector().graph())
+        .await
+        .unwrap();
+
+    // app=client would also select the batch pod, whose own selector is
+    // fine.
+    assert_eq!(sidecars.len(), 1);
+    assert_eq!(sidecars[0].metadata.name.as_deref(), Some("learned-batch"));
+    assert_eq!(
+        sidecars[0].data["spec"]["workloadSelector"]["labels"],
+        json!({"app": "client", "job": "batch"})
+    );
+}
+
+#[tokio::test]
+async fn keeps_reconciling_after_failed_apply() {
+    let server = fake_api_server_with(vec![
+        pod("client-abcde", "default", json!({"app": "client"})),
+        pod("batch-abcde", "batch", json!({"app": "batch"})),
+    ])
+    .await;
+    Mock::given(method("PATCH"))
+        .and(path(
+            "/apis/networking.istio.io/v1beta1/namespaces/client/sidecars/learned-batch",
+        ))
+        .respond_with(ResponseTemplate::new(500))
+        .mount(&server)
+        .await;
+    Mock::given(method("PATCH"))
+        .and(path(
+            "/apis/networking.istio.io/v1beta1/namespaces/client/sidecars/learned-default",
+        ))
+        .respond_with(|request: &wiremock::Request| {
+            ResponseTemplate::new(200).set_body_bytes(request.body.clone())
+        })
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    let err = generator(&server, false)
+        .reconcile(&collector().graph())
+        .await
+        .unwrap_err();
+
+    assert_eq!(
+        err.to_string(),
+        "failed to reconcile sidecars of service accounts client/batch"
+    );
+}
+
+#[tokio::test]
+async fn retries_failed_reconcile_after_backoff() {
+    let server = fake_api_server().await;
+    let sidecar_path =
+        "/apis/networking.istio.io/v1beta1/namespaces/client/sidecars/learned-default";
+    Mock::given(method("PATCH"))
+        .and(path(sidecar_path))
+        .respond_with(ResponseTemplate::new(500))
+        .up_to_n_times(1)
+        .with_priority(1)
+        .mount(&server)
+        .await;
+    Mock::given(method("PATCH"))
+        .and(path(sidecar_path))
+        .respond_with(|request: &wiremock::Request| {
+            ResponseTemplate::new(200).set_body_bytes(request.body.clone())
+        })
+        .mount(&server)
+        .await;
+    let collector = Collector::new(CollectorOptions {
+        retry_delay: Duration::from_millis(10),
+        ..CollectorOptions::default()
+    });
+    collector.record(serde_json::from_str::<EdgeReport>(REPORT).unwrap());
+    let generator = generator(&server, false);
+
+    let patches = || async {
+        server
+            .received_requests()
+            .await
+            .unwrap()
+            .into_iter()
+            .filter(|request| request.method.as_str() == "PATCH")
+            .count()
+    };
+    let retried = async {
+        while patches().await < 2 {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+    };
+    tokio::select! {
+        _ = collector.sync_forever(&generator, None) => unreachable!(),
+        _ = retried => {}
+        _ = tokio::time::sleep(Duration::from_secs(5)) => panic!("failed reconcile was not retried"),
+    }
+}
@@ -0,0 +1,151 @@
+use std::collections::BTreeMap;
+
+use dependency_controller::{
+    edge::EdgeReport,
+    store::{self, ConfigMapStore},
+};
+use kube::{Client, Config};
+use serde_json::json;
+use wiremock::{
+    matchers::{method, path},
+    Mock, MockServer, Request, ResponseTemplate,
+};
+
+const CONFIGMAP_PATH: &str = "/api/v1/namespaces/client/configmaps/learned-dependencies";
+
+const REPORT: &str = r#"{"edges": [
+    {"edge": {"downstream": {"principal": "spiffe://cluster.local/ns/client/sa/default", "trust_domain": "cluster.local", "namespace": "client", "service_account": "default"}, "upstream": {"cluster": "outbound|80||server.server.svc.cluster.local", "direction": "outbound", "port": 80, "host": "server.server.svc.cluster.local"}}, "first_seen": 100, "last_seen": 200, "requests": 3, "errors": 1},
+    {"edge": {"downstream": {"principal": "spiffe://cluster.local/ns/client/sa/batch", "trust_domain": "cluster.local", "namespace": "client", "service_account": "batch"}, "upstream": {"cluster": "outbound|443||api.example.com", "direction": "outbound", "port": 443, "host": "api.example.com"}}, "first_seen": 150, "last_seen": 150},
+    {"edge": {"downstream": {}, "upstream": {"cluster": "outbound|80||server.server.svc.cluster.local", "direction": "outbound", "port": 80, "host": "server.server.svc.cluster.local"}}, "first_seen": 100, "last_seen": 200}
+]}"#;
+
+fn store(server: &MockServer) -> ConfigMapStore {
+    let config = Config::new(server.uri().parse().unwrap());
+    ConfigMapStore::new(Client::try_from(config).unwrap(), "learned-dependencies")
+}
+
+fn update() -> store::NamespaceEdges {
+    let report: EdgeReport = serde_json::from_str(REPORT).unwrap();
+    let mut grouped = store::group_by_namespace(&report.edges);
+    assert_eq!(grouped.keys().collect::<Vec<_>>(), vec!["client"]);
+    grouped.remove("client").unwrap()
+}
+
+fn echo(status: u16) -> impl Fn(&Request) -> ResponseTemplate {
+    move |request: &Request| ResponseTemplate::new(status).set_body_bytes(request.body.clone())
+}
+
+fn configmap(resource_version: &str, data: serde_json::Value) -> serde_json::Value {
+    json!({
+        "apiVersion": "v1",
+        "kind": "ConfigMap",
+        "metadata": {
+            "name": "learned-dependencies",
+            "namespace": "client",
+            "resourceVersion": resource_version,
+        },
+        "data": data,
+    })
+}
+
+async fn written(server: &MockServer, verb: &str) -> Vec<serde_json::Value> {
+    server
+        .received_requests()
+        .await
+        .unwrap()
+        .into_iter()
+        .filter(|request| request.method.as_str() == verb)
+        .map(|request| serde_json::from_slice(&request.body).unwrap())
+        .collect()
+}
+
+#[tokio::test]
+async fn creates_missing_configmap() {
+    let server = MockServer::start().await;
+    Mock::given(method("GET"))
+        .and(path(CONFIGMAP_PATH))
+        .respond_with(ResponseTemplate::new(404).set_body_json(json!({
+            "kind": "Status",
+            "apiVersion": "v1",
+            "status": "Failure",
+            "reason": "NotFound",
+            "code": 404,
+        })))
+        .mount(&server)
+        .await;
+    Mock::given(method("POST"))
+        .and(path("/api/v1/namespaces/client/configmaps"))
+        .respond_with(echo(201))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    store(&server).merge("client", &update()).await.unwrap();
+
+    let created = written(&server, "POST").await;
+    assert_eq!(
+        created[0]["data"],
+        json!({
+            "batch": "outbound|443||api.example.com:\n  first_seen: 150\n  last_seen: 150\n  requests: 0\n  errors: 0\n",
+            "default": "outbound|80||server.server.svc.cluster.local:\n  first_seen: 100\n  last_seen: 200\n  requests: 3\n  errors: 1\n",
+        })
+    );
+}
+
+#[tokio::test]
+async fn retries_conflicting_writes_from_fresh_read() {
+    let server = MockServer::start().await;
+    Mock::given(method("GET"))
+        .and(path(CONFIGMAP_PATH))
+        .respond_with(ResponseTemplate::new(200).set_body_json(configmap("1", json!({}))))
+        .up_to_n_times(1)
+        .with_priority(1)
+        .mount(&server)
+        .await;
+    Mock::given(method("GET"))
+        .and(path(CONFIGMAP_PATH))
+        .respond_with(ResponseTemplate::new(200).set_body_json(configmap(
+            "2",
+            json!({
+                "default": "outbound|80||server.server.svc.cluster.local:\n  first_seen: 50\n  last_seen: 60\n  requests: 7\n  errors: 0\n",
+            }),
+        )))
+        .mount(&server)
+        .await;
+    Mock::given(method("PUT"))
+        .and(path(CONFIGMAP_PATH))
+        .respond_with(ResponseTemplate::new(409).set_body_json(json!({
+            "kind": "Status",
+            "apiVersion": "v1",
+            "status": "Failure",
+            "reason": "Conflict",
+            "code": 409,
+        })))
+        .up_to_n_times(1)
+        .with_priority(1)
+        .mount(&server)
+        .await;
+    Mock::given(method("PUT"))
+        .and(path(CONFIGMAP_PATH))
+        .respond_with(echo(200))
+        .mount(&server)
+        .await;
+
+    store(&server).merge("client", &update()).await.unwrap();
+
+    let replaced = written(&server, "PUT").await;
+    assert_eq!(replaced.len(), 2);
+    assert_eq!(replaced[0]["metadata"]["resourceVersion"], "1");
+    assert_eq!(replaced[1]["metadata"]["resourceVersion"], "2");
+    let data: BTreeMap<String, String> =
+        serde_json::from_value(replaced[1]["data"].clone()).unwrap();
+    assert_eq!(
+        store::parse(&data)["default"]["outbound|80||server.server.svc.cluster.local"],
+        store::EdgeStats {
+            first_seen: 50,
+            last_seen: 200,
+            requests: 10,
+            errors: 1,
+        }
+    );
+}
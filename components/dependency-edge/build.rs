@@ -0,0 +1,34 @@
+/// Generates the protobuf messages of `proto` from `edges.proto`, parsed in
+/// Rust so building does not need protoc.
+fn main() {
+    println!("cargo:rerun-if-changed=build.rs");
+    #[cfg(feature = "proto")]
+    generate_proto();
+}
+
+#[cfg(feature = "proto")]
+fn generate_proto() {
+    use std::{env, fs, path::PathBuf};
+
+    use prost::Message;
+
+    const EDGES_PROTO: &str = "proto/dependency_learner/v1/edges.proto";
+    println!("cargo:rerun-if-changed={EDGES_PROTO}");
+
+    let files = protobuf_parse::Parser::new()
+        .pure()
+        .include("proto")
+        .input(EDGES_PROTO)
+        .file_descriptor_set()
+        .expect("failed to parse edges.proto");
+    let encoded = protobuf::Message::write_to_bytes(&files).expect("failed to encode descriptors");
+    let out_dir = PathBuf::from(env::var("OUT_DIR").expect("OUT_DIR is set by cargo"));
+    fs::write(out_dir.join("edges.bin"), &encoded).expect("failed to write descriptors");
+
+    let files = prost_types::FileDescriptorSet::decode(encoded.as_slice())
+        .expect("failed to decode descriptors");
+    prost_build::Config::new()
+        .btree_map(["."])
+        .compile_fds(files)
+        .expect("failed to generate edges.proto messages");
+}
@@ -0,0 +1,134 @@
+//! Envoy cluster names, as generated by Istio.
+
+use serde::{Deserialize, Serialize};
+
+/// Direction of traffic through the sidecar.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+#[serde(rename_all = "lowercase")]
+pub enum Direction {
+    Inbound,
+    Outbound,
+}
+
+impl Direction {
+    fn parse(direction: &str) -> Option<Self> {
+        match direction {
+            "inbound" => Some(Self::Inbound),
+            "outbound" => Some(Self::Outbound),
+            _ => None,
+        }
+    }
+}
+
+/// What a cluster sends traffic to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+#[serde(rename_all = "snake_case")]
+pub enum ClusterKind {
+    /// A service known to the mesh.
+    Service,
+    /// `BlackHoleCluster`, which drops traffic to unknown destinations.
+    BlackHole,
+    /// `PassthroughCluster` and its inbound variants, which forward traffic
+    /// to unknown destinations as is.
+    Passthrough,
+    /// Anything not generated by Istio.
+    Other,
+}
+
+/// The parts of a cluster name.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ClusterName<'a> {
+    pub kind: ClusterKind,
+    pub direction: Option<Direction>,
+    pub port: Option<u16>,
+    pub subset: Option<&'a str>,
+    /// Fully qualified host, e.g. `server.ns.svc.cluster.local`.
+    pub host: Option<&'a str>,
+    /// Service and namespace, for hosts of the form
+    /// `<service>.<namespace>.svc.<domain>`.
+    pub service: Option<&'a str>,
+    pub namespace: Option<&'a str>,
+}
+
+impl<'a> ClusterName<'a> {
+    /// Parses `<direction>|<port>|<subset>|<host>` names, their
+    /// `<direction>_.<port>_.<subset>_.<host>` SNI form and Istio's catch-all
+    /// clusters. Anything else parses as [`ClusterKind::Other`] without parts.
+    pub fn parse(name: &'a str) -> Self {
+        match name {
+            "BlackHoleCluster" => {
+                return Self::catch_all(ClusterKind::BlackHole, Direction::Outbound)
+            }
+            "PassthroughCluster" => {
+                return Self::catch_all(ClusterKind::Passthrough, Direction::Outbound)
+            }
+            _ if name.starts_with("InboundPassthroughCluster") => {
+                return Self::catch_all(ClusterKind::Passthrough, Direction::Inbound)
+            }
+            _ => {}
+        }
+
+        let parts = match name.split('|').collect::<Vec<_>>()[..] {
+            [direction, port, subset, host] => Some((direction, port, subset, host)),
+            _ => match name.splitn(4, "_.").collect::<Vec<_>>()[..] {
+                [direction, port, subset, host] => Some((direction, port, subset, host)),
+                _ => None,
+            },
+        };
+        let Some((Some(direction), Ok(port), subset, host)) =
+            parts.map(|(direction, port, subset, host)| {
+                (Direction::parse(direction), port.parse(), subset, host)
+            })
+        else {
+            return Self::other();
+        };
+
+        let host = Some(host).filter(|host| !host.is_empty());
+        let (service, namespace) = host.and_then(service_host).unzip();
+        Self {
+            kind: ClusterKind::Service,
+            direction: Some(direction),
+            port: Some(port),
+            subset: Some(subset).filter(|subset| !subset.is_empty()),
+            host,
+            service,
+            namespace,
+        }
+    }
+
+    fn catch_all(kind: ClusterKind, direction: Direction) -> Self {
+        Self {
+            direction: Some(direction),
+            ..Self::of_kind(kind)
+        }
+    }
+
+    fn other() -> Self {
+        Self::of_kind(ClusterKind::Other)
+    }
+
+    fn of_kind(kind: ClusterKind) -> Self {
+        Self {
+            kind,
+            direction: None,
+            port: None,
+            subset: None,
+            host: None,
+            service: None,
+            namespace: None,
+        }
+    }
+}
+
+/// Splits a `<service>.<namespace>.svc.<domain>` host into its service and
+/// namespace.
+fn service_host(host: &str) -> Option<(&str, &str)> {
+    match host.split('.').collect::<Vec<_>>()[..] {
+        [service, namespace, "svc", _, ..] if !service.is_empty() && !namespace.is_empty() => {
+            Some((service, namespace))
+        }
+        _ => None,
+    }
+}
@@ -0,0 +1,283 @@
+//! The dependency edge learned by the `dependency-learner` filter, and the
+//! reports it ships them in.
+
+use std::collections::BTreeMap;
+
+use serde::{Deserialize, Serialize};
+
+mod cluster;
+#[cfg(feature = "proto")]
+pub mod proto;
+mod spiffe;
+
+pub use cluster::{ClusterKind, ClusterName, Direction};
+pub use spiffe::{SpiffeError, SpiffeId};
+
+/// A downstream workload calling an upstream cluster.
+#[derive(Debug, Clone, Default, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+pub struct DependencyEdge {
+    pub downstream: Downstream,
+    pub upstream: Upstream,
+    #[serde(default, skip_serializing_if = "Request::is_empty")]
+    pub request: Request,
+}
+
+/// The calling side of an edge.
+#[derive(Debug, Clone, Default, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+pub struct Downstream {
+    /// Principal of the peer certificate, e.g.
+    /// `spiffe://cluster.local/ns/client/sa/default`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub principal: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub trust_domain: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub namespace: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub service_account: Option<String>,
+    /// Whether the principal is a SPIFFE ID of the
+    /// `spiffe://<td>/ns/<ns>/sa/<sa>` shape, which the parts come from.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub conforming: Option<bool>,
+    /// Workload name, e.g. the Deployment, from Istio's metadata exchange.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub workload: Option<String>,
+    /// `app` label of the workload, or its closest equivalent.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub app: Option<String>,
+    /// `version` label of the workload, or its closest equivalent.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub version: Option<String>,
+    /// ID of the cluster the workload runs in, for multi-cluster meshes.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cluster_id: Option<String>,
+    /// Where the identity was taken from.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub source: Option<IdentitySource>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+#[serde(rename_all = "snake_case")]
+pub enum IdentitySource {
+    PeerCertificate,
+    Xfcc,
+    Header,
+    PeerMetadata,
+}
+
+/// The called side of an edge, identified by the Envoy cluster serving it.
+#[derive(Debug, Clone, Default, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+pub struct Upstream {
+    /// Envoy cluster name, e.g. `outbound|80||server.ns.svc.cluster.local`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cluster: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub kind: Option<ClusterKind>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub direction: Option<Direction>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub port: Option<u16>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub host: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub subset: Option<String>,
+    /// Service name, for hosts of the form `<service>.<namespace>.svc.<domain>`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub service: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub namespace: Option<String>,
+}
+
+/// What the downstream asked the upstream for.
+#[derive(Debug, Clone, Default, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+pub struct Request {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub protocol: Option<Protocol>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub method: Option<String>,
+    /// Path template, e.g. `/users/{id}`, rather than the path as requested.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub path: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub authority: Option<String>,
+    /// Server name the downstream asked for in its TLS handshake, for TCP.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sni: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+#[serde(rename_all = "lowercase")]
+pub enum Protocol {
+    Http,
+    Tcp,
+}
+
+impl Request {
+    pub fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+}
+
+impl DependencyEdge {
+    /// The `<principal> -> <cluster>` form edges were originally reported in,
+    /// with `?` standing in for unknown values.
+    pub fn legacy(&self) -> String {
+        format!(
+            "{} -> {}",
+            self.downstream.principal.as_deref().unwrap_or("?"),
+            self.upstream.cluster.as_deref().unwrap_or("?"),
+        )
+    }
+}
+
+impl Downstream {
+    /// Takes the principal from a comma separated list of URI SANs,
+    /// preferring the first SPIFFE ID, see [`SpiffeId::select`]. A principal
+    /// that is not a SPIFFE ID is kept without any parts.
+    pub fn from_uri_sans(uri_sans: &str) -> Self {
+        Self::from_principals(
+            uri_sans
+                .split(',')
+                .map(str::trim)
+                .filter(|san| !san.is_empty()),
+        )
+    }
+
+    /// Takes the principal from a list of URI SANs, preferring the first
+    /// SPIFFE ID.
+    pub fn from_principals<'a>(uri_sans: impl IntoIterator<Item = &'a str>) -> Self {
+        let Some((principal, id)) = SpiffeId::select(uri_sans) else {
+            return Self::default();
+        };
+        let mut downstream = Self {
+            principal: Some(principal.to_string()),
+            conforming: Some(id.is_ok()),
+            ..Self::default()
+        };
+        if let Ok(id) = id {
+            downstream.trust_domain = Some(id.trust_domain.to_string());
+            downstream.namespace = Some(id.namespace.to_string());
+            downstream.service_account = Some(id.service_account.to_string());
+        }
+        downstream
+    }
+}
+
+impl Upstream {
+    /// Splits a cluster name into its parts, see [`ClusterName::parse`].
+    pub fn from_cluster(cluster: &str) -> Self {
+        let name = ClusterName::parse(cluster);
+        Self {
+            cluster: Some(cluster.to_string()),
+            kind: Some(name.kind),
+            direction: name.direction,
+            port: name.port,
+            host: name.host.map(str::to_string),
+            subset: name.subset.map(str::to_string),
+            service: name.service.map(str::to_string),
+            namespace: name.namespace.map(str::to_string),
+        }
+    }
+}
+
+/// Body POSTed to the collector.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+pub struct EdgeReport {
+    pub edges: Vec<ReportedEdge>,
+}
+
+/// A learned edge as reported to the collector.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+pub struct ReportedEdge {
+    pub edge: DependencyEdge,
+    /// Seconds since the Unix epoch at which the edge was first observed.
+    pub first_seen: u64,
+    /// Seconds since the Unix epoch at which the edge was last observed.
+    pub last_seen: u64,
+    /// Requests observed since the previous report, when aggregating.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub requests: Option<u64>,
+    /// Failed requests observed since the previous report, when aggregating.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub errors: Option<u64>,
+    /// How the upstream answered http requests: those observed since the
+    /// previous report when aggregating, else the request the edge is reported
+    /// for.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub outcomes: Option<Outcomes>,
+}
+
+/// How the upstream of an edge answered a number of requests.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+pub struct Outcomes {
+    /// Requests by response status, `0` standing in for requests that got
+    /// no response at all.
+    #[serde(default)]
+    pub statuses: BTreeMap<u32, u64>,
+    /// Requests by Envoy response code details, e.g. `via_upstream` or
+    /// `route_not_found`.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub details: BTreeMap<String, u64>,
+    #[serde(default)]
+    pub latency: LatencyHistogram,
+    /// Bytes received from the downstream, headers included.
+    #[serde(default)]
+    pub request_bytes: u64,
+    /// Bytes sent to the downstream, headers included.
+    #[serde(default)]
+    pub response_bytes: u64,
+}
+
+/// Request durations, bucketed.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+pub struct LatencyHistogram {
+    /// Inclusive upper bounds of the buckets, in milliseconds, ascending.
+    pub bounds_ms: Vec<u64>,
+    /// Requests per bucket, with a last bucket for requests slower than the
+    /// last bound.
+    pub counts: Vec<u64>,
+    /// Sum of the durations, in milliseconds.
+    pub sum_ms: u64,
+}
+
+impl LatencyHistogram {
+    pub const DEFAULT_BOUNDS_MS: &[u64] =
+        &[5, 10, 25, 50, 100, 250, 500, 1_000, 2_500, 5_000, 10_000];
+
+    pub fn with_bounds(bounds_ms: &[u64]) -> Self {
+        Self {
+            bounds_ms: bounds_ms.to_vec(),
+            counts: vec![0; bounds_ms.len() + 1],
+            sum_ms: 0,
+        }
+    }
+
+    pub fn record(&mut self, duration_ms: u64) {
+        let bucket = self.bounds_ms.partition_point(|bound| *bound < duration_ms);
+        if let Some(count) = self.counts.get_mut(bucket) {
+            *count += 1;
+        }
+        self.sum_ms = self.sum_ms.saturating_add(duration_ms);
+    }
+
+    /// Number of recorded durations.
+    pub fn total(&self) -> u64 {
+        self.counts.iter().sum()
+    }
+}
+
+impl Default for LatencyHistogram {
+    fn default() -> Self {
+        Self::with_bounds(Self::DEFAULT_BOUNDS_MS)
+    }
+}
@@ -0,0 +1,212 @@
+//! Protobuf messages of edge reports, as sent to gRPC collectors. Generated
+//! from `proto/dependency_learner/v1/edges.proto` by the build script.
+
+/// Fully qualified name of the collector service.
+pub const EDGE_COLLECTOR_SERVICE: &str = "dependency_learner.v1.EdgeCollector";
+/// Method of [`EDGE_COLLECTOR_SERVICE`] edge reports are sent to.
+pub const REPORT_METHOD: &str = "Report";
+/// Encoded `FileDescriptorSet` of `edges.proto`, to generate services around
+/// these messages.
+pub const FILE_DESCRIPTOR_SET: &[u8] = include_bytes!(concat!(env!("OUT_DIR"), "/edges.bin"));
+
+include!(concat!(env!("OUT_DIR"), "/dependency_learner.v1.rs"));
+
+impl From<&[crate::ReportedEdge]> for EdgeReport {
+    fn from(edges: &[crate::ReportedEdge]) -> Self {
+        Self {
+            edges: edges.iter().map(ReportedEdge::from).collect(),
+        }
+    }
+}
+
+impl From<EdgeReport> for crate::EdgeReport {
+    fn from(report: EdgeReport) -> Self {
+        Self {
+            edges: report.edges.into_iter().map(Into::into).collect(),
+        }
+    }
+}
+
+impl From<&crate::ReportedEdge> for ReportedEdge {
+    fn from(edge: &crate::ReportedEdge) -> Self {
+        Self {
+            edge: Some((&edge.edge).into()),
+            first_seen: edge.first_seen,
+            last_seen: edge.last_seen,
+            requests: edge.requests,
+            errors: edge.errors,
+            outcomes: edge.outcomes.as_ref().map(Into::into),
+        }
+    }
+}
+
+impl From<ReportedEdge> for crate::ReportedEdge {
+    fn from(edge: ReportedEdge) -> Self {
+        Self {
+            edge: edge.edge.map(Into::into).unwrap_or_default(),
+            first_seen: edge.first_seen,
+            last_seen: edge.last_seen,
+            requests: edge.requests,
+            errors: edge.errors,
+            outcomes: edge.outcomes.map(Into::into),
+        }
+    }
+}
+
+impl From<&crate::Outcomes> for Outcomes {
+    fn from(outcomes: &crate::Outcomes) -> Self {
+        Self {
+            statuses: outcomes.statuses.clone(),
+            details: outcomes.details.clone(),
+            latency: Some(LatencyHistogram {
+                bounds_ms: outcomes.latency.bounds_ms.clone(),
+                counts: outcomes.latency.counts.clone(),
+                sum_ms: outcomes.latency.sum_ms,
+            }),
+            request_bytes: outcomes.request_bytes,
+            response_bytes: outcomes.response_bytes,
+        }
+    }
+}
+
+impl From<Outcomes> for crate::Outcomes {
+    fn from(outcomes: Outcomes) -> Self {
+        Self {
+            statuses: outcomes.statuses,
+            details: outcomes.details,
+            latency: outcomes
+                .latency
+                .map(|latency| crate::LatencyHistogram {
+                    bounds_ms: latency.bounds_ms,
+                    counts: latency.counts,
+                    sum_ms: latency.sum_ms,
+                })
+                .unwrap_or_default(),
+            request_bytes: outcomes.request_bytes,
+            response_bytes: outcomes.response_bytes,
+        }
+    }
+}
+
+impl From<&crate::DependencyEdge> for DependencyEdge {
+    fn from(edge: &crate::DependencyEdge) -> Self {
+        let downstream = &edge.downstream;
+        let upstream = &edge.upstream;
+        let request = &edge.request;
+        Self {
+            downstream: Some(Downstream {
+                principal: downstream.principal.clone(),
+                trust_domain: downstream.trust_domain.clone(),
+                namespace: downstream.namespace.clone(),
+                service_account: downstream.service_account.clone(),
+                conforming: downstream.conforming,
+                workload: downstream.workload.clone(),
+                app: downstream.app.clone(),
+                version: downstream.version.clone(),
+                cluster_id: downstream.cluster_id.clone(),
+                source: downstream
+                    .source
+                    .map_or(IdentitySource::Unspecified, |source| match source {
+                        crate::IdentitySource::PeerCertificate => IdentitySource::PeerCertificate,
+                        crate::IdentitySource::Xfcc => IdentitySource::Xfcc,
+                        crate::IdentitySource::Header => IdentitySource::Header,
+                        crate::IdentitySource::PeerMetadata => IdentitySource::PeerMetadata,
+                    }) as i32,
+            }),
+            upstream: Some(Upstream {
+                cluster: upstream.cluster.clone(),
+                kind: upstream
+                    .kind
+                    .map_or(ClusterKind::Unspecified, |kind| match kind {
+                        crate::ClusterKind::Service => ClusterKind::Service,
+                        crate::ClusterKind::BlackHole => ClusterKind::BlackHole,
+                        crate::ClusterKind::Passthrough => ClusterKind::Passthrough,
+                        crate::ClusterKind::Other => ClusterKind::Other,
+                    }) as i32,
+                direction: upstream
+                    .direction
+                    .map_or(Direction::Unspecified, |direction| match direction {
+                        crate::Direction::Inbound => Direction::Inbound,
+                        crate::Direction::Outbound => Direction::Outbound,
+                    }) as i32,
+                port: upstream.port.map(u32::from),
+                host: upstream.host.clone(),
+                subset: upstream.subset.clone(),
+                service: upstream.service.clone(),
+                namespace: upstream.namespace.clone(),
+            }),
+            request: Some(Request {
+                protocol: request.protocol.map_or(
+                    Protocol::Unspecified,
+                    |protocol| match protocol {
+                        crate::Protocol::Http => Protocol::Http,
+                        crate::Protocol::Tcp => Protocol::Tcp,
+                    },
+                ) as i32,
+                method: request.method.clone(),
+                path: request.path.clone(),
+                authority: request.authority.clone(),
+                sni: request.sni.clone(),
+            }),
+        }
+    }
+}
+
+impl From<DependencyEdge> for crate::DependencyEdge {
+    fn from(edge: DependencyEdge) -> Self {
+        let downstream = edge.downstream.unwrap_or_default();
+        let upstream = edge.upstream.unwrap_or_default();
+        let request = edge.request.unwrap_or_default();
+        Self {
+            downstream: crate::Downstream {
+                source: match downstream.source() {
+                    IdentitySource::Unspecified => None,
+                    IdentitySource::PeerCertificate => Some(crate::IdentitySource::PeerCertificate),
+                    IdentitySource::Xfcc => Some(crate::IdentitySource::Xfcc),
+                    IdentitySource::Header => Some(crate::IdentitySource::Header),
+                    IdentitySource::PeerMetadata => Some(crate::IdentitySource::PeerMetadata),
+                },
+                principal: downstream.principal,
+                trust_domain: downstream.trust_domain,
+                namespace: downstream.namespace,
+                service_account: downstream.service_account,
+                conforming: downstream.conforming,
+                workload: downstream.workload,
+                app: downstream.app,
+                version: downstream.version,
+                cluster_id: downstream.cluster_id,
+            },
+            upstream: crate::Upstream {
+                kind: match upstream.kind() {
+                    ClusterKind::Unspecified => None,
+                    ClusterKind::Service => Some(crate::ClusterKind::Service),
+                    ClusterKind::BlackHole => Some(crate::ClusterKind::BlackHole),
+                    ClusterKind::Passthrough => Some(crate::ClusterKind::Passthrough),
+                    ClusterKind::Other => Some(crate::ClusterKind::Other),
+                },
+                direction: match upstream.direction() {
+                    Direction::Unspecified => None,
+                    Direction::Inbound => Some(crate::Direction::Inbound),
+                    Direction::Outbound => Some(crate::Direction::Outbound),
+                },
+                port: upstream.port.and_then(|port| u16::try_from(port).ok()),
+                cluster: upstream.cluster,
+                host: upstream.host,
+                subset: upstream.subset,
+                service: upstream.service,
+                namespace: upstream.namespace,
+            },
+            request: crate::Request {
+                protocol: match request.protocol() {
+                    Protocol::Unspecified => None,
+                    Protocol::Http => Some(crate::Protocol::Http),
+                    Protocol::Tcp => Some(crate::Protocol::Tcp),
+                },
+                method: request.method,
+                path: request.path,
+                authority: request.authority,
+                sni: request.sni,
+            },
+        }
+    }
+}
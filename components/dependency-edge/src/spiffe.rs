@@ -0,0 +1,90 @@
+//! SPIFFE IDs of Kubernetes workloads, as issued by Istio.
+
+use std::fmt;
+
+/// A `spiffe://<trust domain>/ns/<namespace>/sa/<service account>` ID.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SpiffeId<'a> {
+    pub trust_domain: &'a str,
+    pub namespace: &'a str,
+    pub service_account: &'a str,
+}
+
+/// Why a URI is not a workload's SPIFFE ID.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SpiffeError {
+    /// The URI does not use the `spiffe` scheme.
+    Scheme,
+    /// The trust domain is empty or not made of lowercase letters, digits,
+    /// `.`, `-` and `_`.
+    TrustDomain,
+    /// The path is not `/ns/<namespace>/sa/<service account>`, or one of its
+    /// segments is empty, `.`, `..` or contains other characters than
+    /// letters, digits, `.`, `-` and `_`.
+    Path,
+}
+
+impl fmt::Display for SpiffeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Scheme => write!(f, "not a spiffe:// URI"),
+            Self::TrustDomain => write!(f, "invalid trust domain"),
+            Self::Path => write!(f, "path is not /ns/<namespace>/sa/<service account>"),
+        }
+    }
+}
+
+impl std::error::Error for SpiffeError {}
+
+impl<'a> SpiffeId<'a> {
+    pub fn parse(uri: &'a str) -> Result<Self, SpiffeError> {
+        let id = uri.strip_prefix("spiffe://").ok_or(SpiffeError::Scheme)?;
+        let (trust_domain, path) = id.split_once('/').ok_or(SpiffeError::Path)?;
+        if trust_domain.is_empty()
+            || !trust_domain.bytes().all(|b| {
+                b.is_ascii_lowercase() || b.is_ascii_digit() || matches!(b, b'.' | b'-' | b'_')
+            })
+        {
+            return Err(SpiffeError::TrustDomain);
+        }
+        match path.split('/').collect::<Vec<_>>()[..] {
+            ["ns", namespace, "sa", service_account]
+                if valid_segment(namespace) && valid_segment(service_account) =>
+            {
+                Ok(Self {
+                    trust_domain,
+                    namespace,
+                    service_account,
+                })
+            }
+            _ => Err(SpiffeError::Path),
+        }
+    }
+
+    /// Picks the first SPIFFE ID among URI SANs, e.g. those of a peer
+    /// certificate carrying more than one. Returns the error of the first SAN
+    /// if none is a SPIFFE ID.
+    pub fn select(
+        sans: impl IntoIterator<Item = &'a str>,
+    ) -> Option<(&'a str, Result<Self, SpiffeError>)> {
+        let mut first = None;
+        for san in sans {
+            match Self::parse(san) {
+                Ok(id) => return Some((san, Ok(id))),
+                Err(err) => {
+                    first.get_or_insert((san, Err(err)));
+                }
+            }
+        }
+        first
+    }
+}
+
+fn valid_segment(segment: &str) -> bool {
+    !segment.is_empty()
+        && segment != "."
+        && segment != ".."
+        && segment
+            .bytes()
+            .all(|b| b.is_ascii_alphanumeric() || matches!(b, b'.' | b'-' | b'_'))
+}
@@ -0,0 +1,74 @@
+use dependency_edge::{ClusterKind, ClusterName, Direction};
+
+#[test]
+fn parses_outbound_service_cluster() {
+    assert_eq!(
+        ClusterName::parse("outbound|80|v1|server.ns.svc.cluster.local"),
+        ClusterName {
+            kind: ClusterKind::Service,
+            direction: Some(Direction::Outbound),
+            port: Some(80),
+            subset: Some("v1"),
+            host: Some("server.ns.svc.cluster.local"),
+            service: Some("server"),
+            namespace: Some("ns"),
+        }
+    );
+}
+
+#[test]
+fn parses_sni_form() {
+    let name = ClusterName::parse("outbound_.8080_._.server.ns.svc.cluster.local");
+    assert_eq!(name.direction, Some(Direction::Outbound));
+    assert_eq!(name.port, Some(8080));
+    assert_eq!(name.subset, None);
+    assert_eq!(name.service, Some("server"));
+    assert_eq!(name.namespace, Some("ns"));
+}
+
+#[test]
+fn parses_inbound_cluster_without_host() {
+    let name = ClusterName::parse("inbound|8080||");
+    assert_eq!(name.kind, ClusterKind::Service);
+    assert_eq!(name.direction, Some(Direction::Inbound));
+    assert_eq!(name.port, Some(8080));
+    assert_eq!(name.host, None);
+    assert_eq!(name.service, None);
+}
+
+#[test]
+fn keeps_host_of_external_service() {
+    let name = ClusterName::parse("outbound|443||api.example.com");
+    assert_eq!(name.host, Some("api.example.com"));
+    assert_eq!(name.service, None);
+    assert_eq!(name.namespace, None);
+}
+
+#[test]
+fn parses_catch_all_clusters() {
+    let black_hole = ClusterName::parse("BlackHoleCluster");
+    assert_eq!(black_hole.kind, ClusterKind::BlackHole);
+    assert_eq!(black_hole.direction, Some(Direction::Outbound));
+
+    let passthrough = ClusterName::parse("PassthroughCluster");
+    assert_eq!(passthrough.kind, ClusterKind::Passthrough);
+    assert_eq!(passthrough.direction, Some(Direction::Outbound));
+
+    let inbound = ClusterName::parse("InboundPassthroughClusterIpv4");
+    assert_eq!(inbound.kind, ClusterKind::Passthrough);
+    assert_eq!(inbound.direction, Some(Direction::Inbound));
+}
+
+#[test]
+fn does_not_parse_other_clusters() {
+    for name in [
+        "xds-grpc",
+        "outbound|http|v1|server.ns.svc.cluster.local",
+        "sideways|80||server.ns.svc.cluster.local",
+        "a|b|c",
+    ] {
+        let parsed = ClusterName::parse(name);
+        assert_eq!(parsed.kind, ClusterKind::Other, "{}", name);
+        assert_eq!(parsed.host, None, "{}", name);
+    }
+}
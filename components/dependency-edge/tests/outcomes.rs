@@ -0,0 +1,35 @@
+use dependency_edge::{LatencyHistogram, Outcomes, ReportedEdge};
+
+#[test]
+fn buckets_latencies_by_inclusive_upper_bound() {
+    let mut histogram = LatencyHistogram::with_bounds(&[10, 100]);
+    for duration_ms in [0, 10, 11, 100, 101, 5_000] {
+        histogram.record(duration_ms);
+    }
+    assert_eq!(histogram.counts, [2, 2, 2]);
+    assert_eq!(histogram.sum_ms, 5_222);
+    assert_eq!(histogram.total(), 6);
+}
+
+#[test]
+fn defaults_to_default_bounds() {
+    let histogram = LatencyHistogram::default();
+    assert_eq!(histogram.bounds_ms, LatencyHistogram::DEFAULT_BOUNDS_MS);
+    assert_eq!(histogram.counts.len(), histogram.bounds_ms.len() + 1);
+}
+
+#[test]
+fn keys_statuses_by_code_in_json() {
+    let edge: ReportedEdge = serde_json::from_str(
+        r#"{"edge": {"downstream": {}, "upstream": {}}, "first_seen": 1, "last_seen": 2,
+            "outcomes": {"statuses": {"404": 3}}}"#,
+    )
+    .unwrap();
+    let outcomes = edge.outcomes.unwrap();
+    assert_eq!(outcomes.statuses, [(404, 3)].into());
+    assert_eq!(outcomes.latency, LatencyHistogram::default());
+    assert_eq!(
+        serde_json::to_value(Outcomes::default()).unwrap()["statuses"],
+        serde_json::json!({})
+    );
+}
@@ -0,0 +1,97 @@
+#![cfg(feature = "proto")]
+
+use dependency_edge::{
+    proto, DependencyEdge, Downstream, EdgeReport, IdentitySource, Outcomes, Protocol,
+    ReportedEdge, Request, Upstream,
+};
+use prost::Message;
+
+fn reported(edge: DependencyEdge) -> ReportedEdge {
+    ReportedEdge {
+        edge,
+        first_seen: 1_700_000_000,
+        last_seen: 1_700_000_060,
+        requests: Some(3),
+        errors: None,
+        outcomes: None,
+    }
+}
+
+#[test]
+fn round_trips_through_protobuf() {
+    let edges = [
+        reported(DependencyEdge {
+            downstream: Downstream {
+                source: Some(IdentitySource::PeerCertificate),
+                workload: Some("client-v1".to_string()),
+                ..Downstream::from_uri_sans("spiffe://cluster.local/ns/client/sa/default")
+            },
+            upstream: Upstream::from_cluster("outbound|80|v1|server.server.svc.cluster.local"),
+            request: Request {
+                protocol: Some(Protocol::Http),
+                method: Some("GET".to_string()),
+                path: Some("/users/{id}".to_string()),
+                ..Request::default()
+            },
+        }),
+        ReportedEdge {
+            outcomes: Some(outcomes()),
+            ..reported(DependencyEdge::default())
+        },
+    ];
+    let encoded = proto::EdgeReport::from(&edges[..]).encode_to_vec();
+    let decoded = EdgeReport::from(proto::EdgeReport::decode(&encoded[..]).unwrap());
+    assert_eq!(decoded.edges.len(), 2);
+    for (decoded, edge) in decoded.edges.iter().zip(&edges) {
+        assert_eq!(decoded.edge, edge.edge);
+        assert_eq!(decoded.first_seen, edge.first_seen);
+        assert_eq!(decoded.last_seen, edge.last_seen);
+        assert_eq!(decoded.requests, edge.requests);
+        assert_eq!(decoded.errors, edge.errors);
+        assert_eq!(decoded.outcomes, edge.outcomes);
+    }
+}
+
+fn outcomes() -> Outcomes {
+    let mut outcomes = Outcomes {
+        statuses: [(200, 2), (404, 1)].into(),
+        details: [("via_upstream".to_string(), 3)].into(),
+        request_bytes: 512,
+        response_bytes: 2_048,
+        ..Outcomes::default()
+    };
+    for duration_ms in [3, 40, 12_000] {
+        outcomes.latency.record(duration_ms);
+    }
+    outcomes
+}
+
+#[test]
+fn leaves_unknown_enum_values_unset() {
+    let edge = proto::DependencyEdge {
+        downstream: None,
+        upstream: Some(proto::Upstream {
+            kind: 42,
+            ..proto::Upstream::default()
+        }),
+        request: None,
+    };
+    assert_eq!(DependencyEdge::from(edge), DependencyEdge::default());
+}
+
+#[test]
+fn describes_collector_service() {
+    let files = prost_types::FileDescriptorSet::decode(proto::FILE_DESCRIPTOR_SET).unwrap();
+    let [file] = files.file.as_slice() else {
+        panic!("expected only edges.proto, got {:?}", files.file);
+    };
+    let [service] = file.service.as_slice() else {
+        panic!("expected a single service, got {:?}", file.service);
+    };
+    assert_eq!(
+        format!("{}.{}", file.package(), service.name()),
+        proto::EDGE_COLLECTOR_SERVICE
+    );
+    let methods: Vec<_> = service.method.iter().map(|method| method.name()).collect();
+    assert_eq!(methods, [proto::REPORT_METHOD]);
+}
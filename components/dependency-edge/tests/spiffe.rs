@@ -0,0 +1,72 @@
+use dependency_edge::{Downstream, SpiffeError, SpiffeId};
+
+#[test]
+fn parses_workload_id() {
+    assert_eq!(
+        SpiffeId::parse("spiffe://cluster.local/ns/client/sa/default"),
+        Ok(SpiffeId {
+            trust_domain: "cluster.local",
+            namespace: "client",
+            service_account: "default",
+        })
+    );
+}
+
+#[test]
+fn rejects_non_conforming_ids() {
+    for (uri, err) in [
+        (
+            "https://cluster.local/ns/client/sa/default",
+            SpiffeError::Scheme,
+        ),
+        ("spiffe://cluster.local", SpiffeError::Path),
+        ("spiffe:///ns/client/sa/default", SpiffeError::TrustDomain),
+        (
+            "spiffe://Cluster.Local/ns/client/sa/default",
+            SpiffeError::TrustDomain,
+        ),
+        (
+            "spiffe://cluster.local:443/ns/client/sa/default",
+            SpiffeError::TrustDomain,
+        ),
+        ("spiffe://cluster.local/ns/client", SpiffeError::Path),
+        ("spiffe://cluster.local/ns/client/sa/", SpiffeError::Path),
+        ("spiffe://cluster.local/ns/../sa/default", SpiffeError::Path),
+        (
+            "spiffe://cluster.local/ns/client/sa/default/extra",
+            SpiffeError::Path,
+        ),
+        (
+            "spiffe://cluster.local/ns/client/sa/default?x=1",
+            SpiffeError::Path,
+        ),
+        ("spiffe://cluster.local/workload/client", SpiffeError::Path),
+    ] {
+        assert_eq!(SpiffeId::parse(uri), Err(err), "{}", uri);
+    }
+}
+
+#[test]
+fn prefers_spiffe_id_among_uri_sans() {
+    let downstream =
+        Downstream::from_uri_sans("urn:example:client, spiffe://cluster.local/ns/client/sa/batch");
+    assert_eq!(
+        downstream.principal.as_deref(),
+        Some("spiffe://cluster.local/ns/client/sa/batch")
+    );
+    assert_eq!(downstream.conforming, Some(true));
+    assert_eq!(downstream.namespace.as_deref(), Some("client"));
+    assert_eq!(downstream.service_account.as_deref(), Some("batch"));
+}
+
+#[test]
+fn flags_non_conforming_principal() {
+    let downstream = Downstream::from_uri_sans("spiffe://example.org/workload/client");
+    assert_eq!(
+        downstream.principal.as_deref(),
+        Some("spiffe://example.org/workload/client")
+    );
+    assert_eq!(downstream.conforming, Some(false));
+    assert_eq!(downstream.trust_domain, None);
+    assert_eq!(downstream.namespace, None);
+}
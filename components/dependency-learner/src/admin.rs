@@ -0,0 +1,94 @@
+use dependency_edge::EdgeReport;
+use log::warn;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use crate::{dedup, host::HttpHost, identity, validate::Validator};
+
+fn default_path() -> String {
+    "/.well-known/dependency-learner".to_string()
+}
+
+/// A path answered by the filter itself with the edges the VM knows, for
+/// debugging without a collector.
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct AdminConfig {
+    /// Path the edges are served on, to GET requests. Requests to it are not
+    /// forwarded; other methods are answered with a 405.
+    #[serde(default = "default_path")]
+    pub path: String,
+    /// SPIFFE IDs of downstreams allowed to read the edges, as presented in
+    /// their peer certificate. Identities from the other `identity_sources`
+    /// can be set by any client and are not trusted here. Other downstreams
+    /// are answered with a 403.
+    pub principals: Vec<String>,
+}
+
+/// Body of responses to requests not allowed to read the edges.
+#[derive(Debug, Serialize)]
+struct Refusal {
+    error: &'static str,
+    message: &'static str,
+}
+
+impl AdminConfig {
+    pub fn validate(&self, path: &str, validator: &mut Validator) {
+        validator.url_path(format!("{}.path", path), &self.path);
+        validator.check(
+            format!("{}.path", path),
+            !self.path.contains('?'),
+            "must not have a query",
+        );
+        validator.check(
+            format!("{}.principals", path),
+            !self.principals.is_empty(),
+            "must allow at least one principal",
+        );
+        for (i, principal) in self.principals.iter().enumerate() {
+            validator.spiffe_id(format!("{}.principals[{}]", path, i), principal);
+        }
+    }
+
+    /// Whether a request for `path`, as requested, is for the edges.
+    pub fn matches(&self, path: &str) -> bool {
+        path.split_once('?').map_or(path, |(path, _)| path) == self.path
+    }
+
+    /// Answers GET requests with the edges deduplicated in shared data, as an
+    /// [`EdgeReport`], if the principal of the peer certificate may read
+    /// them.
+    pub fn respond(&self, host: &impl HttpHost) {
+        let headers = vec![
+            ("content-type", "application/json"),
+            ("cache-control", "no-store"),
+        ];
+        if host.request_header(":method").as_deref() != Some("GET") {
+            let body = refusal("method_not_allowed", "learned edges are only served to GET");
+            host.send_response(405, [headers, vec![("allow", "GET")]].concat(), Some(&body));
+            return;
+        }
+        let principal = identity::peer_certificate(host).and_then(|peer| peer.principal);
+        if !principal
+            .as_ref()
+            .is_some_and(|principal| self.principals.contains(principal))
+        {
+            warn!(
+                "Refusing edges to downstream {}",
+                principal.as_deref().unwrap_or("without a peer certificate")
+            );
+            let body = refusal("forbidden", "the downstream may not read learned edges");
+            host.send_response(403, headers, Some(&body));
+            return;
+        }
+        let report = EdgeReport {
+            edges: dedup::snapshot(host),
+        };
+        let body = serde_json::to_vec(&report).expect("edge report is serializable");
+        host.send_response(200, headers, Some(&body));
+    }
+}
+
+fn refusal(error: &'static str, message: &'static str) -> Vec<u8> {
+    serde_json::to_vec(&Refusal { error, message }).expect("error is serializable")
+}
@@ -0,0 +1,245 @@
+use std::{
+    collections::BTreeMap,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use dependency_edge::{DependencyEdge, Outcomes};
+use log::{info, warn};
+use proxy_wasm::types::Status;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use crate::{host::Host, outcome::Outcome, validate::Validator};
+
+/// Upper bound on observations drained per tick, so a flood of traffic cannot
+/// stall the root context indefinitely.
+const MAX_DRAINED_PER_TICK: usize = 100_000;
+
+/// Number of flush intervals a drain lease lasts without being renewed.
+const LEASE_INTERVALS: u32 = 3;
+const MAX_CAS_ATTEMPTS: usize = 8;
+
+fn default_flush_interval_ms() -> u64 {
+    10_000
+}
+
+fn default_queue() -> String {
+    "dependency-learner".to_string()
+}
+
+/// Reports aggregates on a fixed interval instead of individual edges. A
+/// single worker drains the observations of all, see [`DrainLease`].
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct AggregationConfig {
+    #[serde(default = "default_flush_interval_ms")]
+    pub flush_interval_ms: u64,
+    /// Name of the shared queue http contexts push observations to.
+    #[serde(default = "default_queue")]
+    pub queue: String,
+}
+
+impl AggregationConfig {
+    pub fn validate(&self, path: &str, validator: &mut Validator) {
+        validator.duration_ms(
+            format!("{}.flush_interval_ms", path),
+            self.flush_interval_ms,
+        );
+        validator.check(
+            format!("{}.queue", path),
+            !self.queue.is_empty(),
+            "must name a shared queue",
+        );
+    }
+}
+
+/// A single request as seen by an http context.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct EdgeObservation {
+    pub edge: DependencyEdge,
+    /// Whether the upstream answered with a 5xx or without a status at all.
+    pub error: bool,
+    /// How the upstream answered, for http requests that were logged.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub outcome: Option<Outcome>,
+}
+
+impl EdgeObservation {
+    pub fn enqueue(&self, host: &impl Host, queue_id: u32) {
+        let value = serde_json::to_vec(self).expect("edge observation is serializable");
+        if let Err(status) = host.enqueue_shared_queue(queue_id, &value) {
+            warn!("Failed to enqueue edge observation: {:?}", status);
+        }
+    }
+}
+
+#[derive(Debug, Default, Clone)]
+pub struct EdgeCounts {
+    pub requests: u64,
+    pub errors: u64,
+    /// Outcomes of the requests that have one.
+    pub outcomes: Option<Outcomes>,
+}
+
+/// Drains the queue, counting requests, errors and their outcomes per edge.
+pub fn drain(host: &impl Host, queue_id: u32) -> BTreeMap<DependencyEdge, EdgeCounts> {
+    let mut aggregates = BTreeMap::<_, EdgeCounts>::new();
+    for _ in 0..MAX_DRAINED_PER_TICK {
+        let raw = match host.dequeue_shared_queue(queue_id) {
+            Ok(Some(raw)) => raw,
+            Ok(None) => break,
+            Err(status) => {
+                warn!("Failed to dequeue edge observation: {:?}", status);
+                break;
+            }
+        };
+        let observation = match serde_json::from_slice::<EdgeObservation>(&raw) {
+            Ok(observation) => observation,
+            Err(err) => {
+                warn!("Discarding malformed edge observation: {}", err);
+                continue;
+            }
+        };
+        let counts = aggregates.entry(observation.edge).or_default();
+        counts.requests += 1;
+        if observation.error {
+            counts.errors += 1;
+        }
+        if let Some(outcome) = observation.outcome {
+            outcome.record(counts.outcomes.get_or_insert_with(Outcomes::default));
+        }
+    }
+    aggregates
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+struct Lease {
+    holder: u64,
+    /// Milliseconds since the Unix epoch at which the lease lapses.
+    until_ms: u64,
+}
+
+/// Elects the root context that drains the queue. Every worker thread has
+/// a root of its own, and were each to drain the queue an interval's
+/// observations would be split over several reports of the same edge.
+///
+/// The holder renews the lease on every flush. It passes to another root
+/// once it has not been renewed for a few intervals, e.g. as the holder's
+/// worker is gone.
+pub struct DrainLease {
+    key: String,
+    holders_key: String,
+    duration: Duration,
+    holder: Option<u64>,
+}
+
+impl DrainLease {
+    pub fn new(config: &AggregationConfig) -> Self {
+        Self {
+            key: format!("{}.drain_lease", config.queue),
+            holders_key: format!("{}.drain_lease.holders", config.queue),
+            duration: Duration::from_millis(config.flush_interval_ms) * LEASE_INTERVALS,
+            holder: None,
+        }
+    }
+
+    /// Takes or renews the lease, returning whether this root holds it and
+    /// should therefore drain the queue.
+    pub fn acquire(&mut self, host: &impl Host, now: SystemTime) -> bool {
+        let Some(holder) = self.holder(host) else {
+            return false;
+        };
+        let now_ms = unix_millis(now);
+        let (raw, cas) = host.versioned_shared_data(&self.key);
+        let lease = raw.and_then(|raw| {
+            serde_json::from_slice::<Lease>(&raw)
+                .inspect_err(|err| warn!("Discarding malformed {}: {}", self.key, err))
+                .ok()
+        });
+        if lease.is_some_and(|lease| lease.holder != holder && now_ms < lease.until_ms) {
+            return false;
+        }
+        let renewed = Lease {
+            holder,
+            until_ms: now_ms.saturating_add(self.duration.as_millis() as u64),
+        };
+        let value = serde_json::to_vec(&renewed).expect("lease is serializable");
+        match host.set_versioned_shared_data(&self.key, &value, cas) {
+            Ok(()) => {
+                if lease.is_none_or(|lease| lease.holder != holder) {
+                    info!("Draining {} as holder {}", self.key, holder);
+                }
+                true
+            }
+            // Another root took the lease first.
+            Err(Status::CasMismatch) => false,
+            Err(status) => {
+                warn!("Failed to update {}: {:?}", self.key, status);
+                false
+            }
+        }
+    }
+
+    /// ID of this root among those contending for the lease, taken from a
+    /// counter in shared data the first time it is needed.
+    fn holder(&mut self, host: &impl Host) -> Option<u64> {
+        if self.holder.is_some() {
+            return self.holder;
+        }
+        for _ in 0..MAX_CAS_ATTEMPTS {
+            let (raw, cas) = host.versioned_shared_data(&self.holders_key);
+            let holder = raw
+                .and_then(|raw| serde_json::from_slice::<u64>(&raw).ok())
+                .unwrap_or_default()
+                + 1;
+            let value = serde_json::to_vec(&holder).expect("holder is serializable");
+            match host.set_versioned_shared_data(&self.holders_key, &value, cas) {
+                Ok(()) => {
+                    self.holder = Some(holder);
+                    return self.holder;
+                }
+                Err(Status::CasMismatch) => continue,
+                Err(status) => {
+                    warn!("Failed to update {}: {:?}", self.holders_key, status);
+                    return None;
+                }
+            }
+        }
+        warn!(
+            "Gave up updating {} after concurrent modifications",
+            self.holders_key
+        );
+        None
+    }
+}
+
+fn unix_millis(time: SystemTime) -> u64 {
+    time.duration_since(UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_millis() as u64)
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::harness::FakeHost;
+
+    #[test]
+    fn elects_single_drainer_of_queue() {
+        let config = AggregationConfig {
+            flush_interval_ms: 1_000,
+            queue: "dependency-learner".to_string(),
+        };
+        let host = FakeHost::default();
+        let at = |secs| host.now + Duration::from_secs(secs);
+        let mut first = DrainLease::new(&config);
+        let mut second = DrainLease::new(&config);
+        assert!(first.acquire(&host, at(0)));
+        assert!(!second.acquire(&host, at(0)));
+        assert!(first.acquire(&host, at(1)));
+        assert!(!second.acquire(&host, at(3)));
+        // The first root stopped renewing its lease.
+        assert!(second.acquire(&host, at(4)));
+        assert!(!first.acquire(&host, at(5)));
+    }
+}
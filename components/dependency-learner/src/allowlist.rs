@@ -0,0 +1,306 @@
+use dependency_edge::{ClusterKind, DependencyEdge, Direction, Protocol};
+use log::{info, warn};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::{host::Host, validate::Validator};
+
+fn default_shared_data_key() -> String {
+    "dependency-learner.allowlist".to_string()
+}
+
+fn default_refresh_interval_ms() -> u64 {
+    10_000
+}
+
+fn default_audit_header() -> String {
+    "x-dependency-learner-undeclared".to_string()
+}
+
+/// What the learner does with edges missing from the allowlist.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum Mode {
+    /// Only learn edges.
+    #[default]
+    Learn,
+    /// Tag requests of undeclared edges with the audit header.
+    Audit,
+    /// Reject requests of undeclared edges with a 403. Only applies to the
+    /// http filter; the network filter keeps learning.
+    Enforce,
+}
+
+/// A declared edge. Fields that are left out match any value, so
+/// `{"downstream": {"namespace": "client"}}` allows everything the `client`
+/// namespace calls.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct EdgePattern {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub downstream: Option<DownstreamPattern>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub upstream: Option<UpstreamPattern>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub request: Option<RequestPattern>,
+}
+
+/// Identity fields of [`dependency_edge::Downstream`] to match.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct DownstreamPattern {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub principal: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub trust_domain: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub namespace: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub service_account: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub workload: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub app: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub version: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cluster_id: Option<String>,
+}
+
+/// Fields of [`dependency_edge::Upstream`] to match.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct UpstreamPattern {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cluster: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub kind: Option<ClusterKind>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub direction: Option<Direction>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub port: Option<u16>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub host: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub subset: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub service: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub namespace: Option<String>,
+}
+
+/// Fields of [`dependency_edge::Request`] to match.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct RequestPattern {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub protocol: Option<Protocol>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub method: Option<String>,
+    /// Path template, e.g. `/users/{id}`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub path: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub authority: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sni: Option<String>,
+}
+
+impl EdgePattern {
+    /// Whether the pattern leaves every field out, and so matches any edge.
+    fn is_empty(&self) -> bool {
+        self.downstream
+            .as_ref()
+            .is_none_or(|downstream| *downstream == DownstreamPattern::default())
+            && self
+                .upstream
+                .as_ref()
+                .is_none_or(|upstream| *upstream == UpstreamPattern::default())
+            && self
+                .request
+                .as_ref()
+                .is_none_or(|request| *request == RequestPattern::default())
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct AllowlistConfig {
+    /// Declared edges.
+    #[serde(default)]
+    pub edges: Vec<EdgePattern>,
+    /// Shared data key holding a JSON list of edge patterns that, once set,
+    /// replaces `edges`. The learner only reads it: it is for another plugin
+    /// of the same VM, i.e. with the same `vm_id`, to write, e.g. one that
+    /// fetches the allowlist from a config service. Lists that are malformed
+    /// or hold a pattern matching no field are ignored.
+    #[serde(default = "default_shared_data_key")]
+    pub shared_data_key: String,
+    #[serde(default = "default_refresh_interval_ms")]
+    pub refresh_interval_ms: u64,
+    /// Request header set to `true` on undeclared edges in audit mode.
+    #[serde(default = "default_audit_header")]
+    pub audit_header: String,
+}
+
+impl AllowlistConfig {
+    pub fn validate(&self, path: &str, validator: &mut Validator) {
+        validator.check(
+            format!("{}.shared_data_key", path),
+            !self.shared_data_key.is_empty(),
+            "must name a shared data key",
+        );
+        for (i, edge) in self.edges.iter().enumerate() {
+            validator.check(
+                format!("{}.edges[{}]", path, i),
+                !edge.is_empty(),
+                "must match at least one field, or it allows every edge",
+            );
+        }
+        validator.duration_ms(
+            format!("{}.refresh_interval_ms", path),
+            self.refresh_interval_ms,
+        );
+        validator.header_name(format!("{}.audit_header", path), &self.audit_header);
+    }
+}
+
+/// Body of the 403 answering undeclared edges in enforce mode.
+#[derive(Debug, Serialize)]
+pub struct Denial<'a> {
+    pub error: &'static str,
+    pub message: &'static str,
+    pub edge: &'a DependencyEdge,
+}
+
+impl<'a> Denial<'a> {
+    pub fn new(edge: &'a DependencyEdge) -> Self {
+        Self {
+            error: "undeclared_dependency",
+            message: "the dependency is not in the allowlist",
+            edge,
+        }
+    }
+}
+
+/// Declared edges, kept as JSON so a pattern matches an edge when it is a
+/// subset of it.
+pub struct Allowlist {
+    patterns: Vec<Value>,
+    shared_data_key: String,
+    cas: Option<u32>,
+}
+
+impl Allowlist {
+    pub fn new(config: &AllowlistConfig) -> Self {
+        Self {
+            patterns: patterns(&config.edges),
+            shared_data_key: config.shared_data_key.clone(),
+            cas: None,
+        }
+    }
+
+    pub fn allows(&self, edge: &DependencyEdge) -> bool {
+        let edge = serde_json::to_value(edge).expect("edge is serializable");
+        self.patterns
+            .iter()
+            .any(|pattern| is_subset(pattern, &edge))
+    }
+
+    /// Replaces the edges with those in shared data, if they changed. Lists
+    /// that fail validation are ignored, keeping the edges allowed so far.
+    pub fn refresh(&mut self, host: &impl Host) {
+        let (Some(raw), cas) = host.versioned_shared_data(&self.shared_data_key) else {
+            return;
+        };
+        if cas.is_some() && cas == self.cas {
+            return;
+        }
+        match serde_json::from_slice::<Vec<EdgePattern>>(&raw) {
+            Ok(edges) if edges.iter().any(EdgePattern::is_empty) => warn!(
+                "Ignoring allowlist in {}: a pattern matching no field allows every edge",
+                self.shared_data_key
+            ),
+            Ok(edges) => {
+                info!(
+                    "Loaded {} allowed edges from {}",
+                    edges.len(),
+                    self.shared_data_key
+                );
+                self.patterns = patterns(&edges);
+                self.cas = cas;
+            }
+            Err(err) => warn!(
+                "Ignoring malformed allowlist in {}: {}",
+                self.shared_data_key, err
+            ),
+        }
+    }
+}
+
+fn patterns(edges: &[EdgePattern]) -> Vec<Value> {
+    edges
+        .iter()
+        .map(|edge| serde_json::to_value(edge).expect("edge pattern is serializable"))
+        .collect()
+}
+
+fn is_subset(pattern: &Value, value: &Value) -> bool {
+    match (pattern, value) {
+        (Value::Object(pattern), Value::Object(value)) => pattern.iter().all(|(key, pattern)| {
+            value
+                .get(key)
+                .is_some_and(|value| is_subset(pattern, value))
+        }),
+        _ => pattern == value,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use dependency_edge::{Downstream, Request, Upstream};
+
+    use super::*;
+    use crate::harness::FakeHost;
+
+    fn edge(namespace: &str) -> DependencyEdge {
+        DependencyEdge {
+            downstream: Downstream::from_uri_sans(&format!(
+                "spiffe://cluster.local/ns/{}/sa/default",
+                namespace
+            )),
+            upstream: Upstream::from_cluster("outbound|80||server.server.svc.cluster.local"),
+            request: Request::default(),
+        }
+    }
+
+    #[test]
+    fn refreshes_edges_from_shared_data() {
+        let config: AllowlistConfig =
+            serde_json::from_str(r#"{"edges": [{"downstream": {"namespace": "client"}}]}"#)
+                .unwrap();
+        let mut allowlist = Allowlist::new(&config);
+        let host = FakeHost::default();
+        allowlist.refresh(&host);
+        assert!(allowlist.allows(&edge("client")));
+
+        host.set_shared_data(
+            "dependency-learner.allowlist",
+            br#"[{"downstream": {"namespace": "batch"}}]"#,
+        );
+        allowlist.refresh(&host);
+        assert!(allowlist.allows(&edge("batch")));
+        assert!(!allowlist.allows(&edge("client")));
+
+        for invalid in [
+            &br#"[{"downstream": {"namepsace": "client"}}]"#[..],
+            b"[{}]",
+        ] {
+            host.set_shared_data("dependency-learner.allowlist", invalid);
+            allowlist.refresh(&host);
+            assert!(allowlist.allows(&edge("batch")));
+            assert!(!allowlist.allows(&edge("client")));
+        }
+    }
+}
@@ -0,0 +1,70 @@
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::{host::HttpHost, validate::Validator};
+
+const REQUEST_ID_HEADER: &str = "x-request-id";
+
+/// Limits the response header to requests carrying a debug trigger, so that
+/// mesh topology is not disclosed to every caller. Any one trigger suffices.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct DebugConfig {
+    pub header: Option<DebugHeader>,
+    /// SPIFFE IDs of downstreams that always get the header.
+    #[serde(default)]
+    pub principals: Vec<String>,
+    /// Share of requests, in percent, that get the header. Sampled by
+    /// `x-request-id`, so requests without one are never sampled.
+    #[serde(default)]
+    pub sample_percent: f64,
+}
+
+/// Request header that triggers the response header.
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct DebugHeader {
+    pub name: String,
+    /// Required value. Any value triggers when unset.
+    pub value: Option<String>,
+}
+
+impl DebugConfig {
+    pub fn validate(&self, path: &str, validator: &mut Validator) {
+        if let Some(header) = self.header.as_ref() {
+            validator.header_name(format!("{}.header.name", path), &header.name);
+        }
+        for (i, principal) in self.principals.iter().enumerate() {
+            validator.spiffe_id(format!("{}.principals[{}]", path, i), principal);
+        }
+        validator.check(
+            format!("{}.sample_percent", path),
+            (0.0..=100.0).contains(&self.sample_percent),
+            "must be between 0 and 100",
+        );
+    }
+
+    pub fn triggered(&self, host: &impl HttpHost, principal: Option<&str>) -> bool {
+        if let Some(header) = self.header.as_ref() {
+            let value = host.request_header(&header.name);
+            if value.is_some_and(|value| header.value.as_ref().is_none_or(|want| *want == value)) {
+                return true;
+            }
+        }
+        if principal.is_some_and(|principal| self.principals.iter().any(|p| p == principal)) {
+            return true;
+        }
+        self.sample_percent > 0.0
+            && host
+                .request_header(REQUEST_ID_HEADER)
+                .is_some_and(|request_id| sampled(&request_id, self.sample_percent))
+    }
+}
+
+/// Whether a request falls within `percent`, consistently for a request ID.
+fn sampled(request_id: &str, percent: f64) -> bool {
+    let digest = Sha256::digest(request_id.as_bytes());
+    let bucket = u64::from_be_bytes(digest[..8].try_into().expect("digest is 32 bytes"));
+    ((bucket % 10_000) as f64) < percent * 100.0
+}
@@ -0,0 +1,298 @@
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use dependency_edge::{DependencyEdge, ReportedEdge};
+use log::warn;
+use proxy_wasm::types::Status;
+use serde::{de::IgnoredAny, Deserialize, Serialize};
+
+use crate::host::Host;
+
+const EDGE_KEY_PREFIX: &str = "dependency-learner.edge.";
+const EDGE_INDEX_KEY: &str = "dependency-learner.edges";
+const MAX_CAS_ATTEMPTS: usize = 8;
+
+/// What the VM knows about an edge, shared between all of its worker threads.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct EdgeRecord {
+    /// Seconds since the Unix epoch at which the edge was first observed.
+    pub first_seen: u64,
+    /// Seconds since the Unix epoch at which the edge was last observed.
+    pub last_seen: u64,
+    /// Seconds since the Unix epoch at which the edge was last emitted.
+    pub last_emitted: u64,
+}
+
+/// Value of an edge's key: its record, and the edge itself so that the VM's
+/// edges can be listed, see [`snapshot`].
+#[derive(Debug, Serialize, Deserialize)]
+struct StoredEdge<E> {
+    #[serde(flatten)]
+    record: EdgeRecord,
+    edge: E,
+}
+
+/// Outcome of observing an edge.
+#[derive(Debug, Clone, Copy)]
+pub struct Observation {
+    pub record: EdgeRecord,
+    /// Whether the edge has not been emitted within the TTL and should
+    /// therefore be emitted now.
+    pub emit: bool,
+}
+
+/// Deduplicates edges across the VM through proxy-wasm shared data.
+///
+/// Edges are keyed on their downstream identity and upstream cluster, so
+/// requests differing only in method, path or authority count as one edge and
+/// the number of keys is bounded by the mesh rather than by traffic. Each edge
+/// has its own key so concurrent updates to different edges never conflict.
+/// Keys of known edges are additionally listed under a single index key, as
+/// shared data cannot be enumerated.
+pub struct EdgeDedup {
+    ttl: Duration,
+}
+
+impl EdgeDedup {
+    pub fn new(ttl: Duration) -> Self {
+        Self { ttl }
+    }
+
+    /// Records an observation of the edge. Returns `None` if shared data could
+    /// not be updated.
+    ///
+    /// Shared data is only written when the edge is emitted or its last-seen
+    /// second moves on, so busy edges cost at most one write per second.
+    pub fn observe(
+        &self,
+        host: &impl Host,
+        edge: &DependencyEdge,
+        now: SystemTime,
+    ) -> Option<Observation> {
+        let now = unix_seconds(now);
+        let key = edge_key(edge);
+        for _ in 0..MAX_CAS_ATTEMPTS {
+            let (raw, cas) = host.versioned_shared_data(&key);
+            let previous = raw.and_then(|raw| {
+                serde_json::from_slice::<StoredEdge<IgnoredAny>>(&raw)
+                    .inspect_err(|err| warn!("Discarding malformed record for {}: {}", key, err))
+                    .ok()
+                    .map(|stored| stored.record)
+            });
+            let emit = previous.is_none_or(|previous| {
+                now.saturating_sub(previous.last_emitted) >= self.ttl.as_secs()
+            });
+            if let Some(previous) = previous.filter(|previous| !emit && previous.last_seen >= now) {
+                return Some(Observation {
+                    record: previous,
+                    emit,
+                });
+            }
+            let record = EdgeRecord {
+                first_seen: previous.map_or(now, |previous| previous.first_seen),
+                last_seen: now,
+                last_emitted: match previous {
+                    Some(previous) if !emit => previous.last_emitted,
+                    _ => now,
+                },
+            };
+            let value = serde_json::to_vec(&StoredEdge { record, edge })
+                .expect("edge record is serializable");
+            match host.set_versioned_shared_data(&key, &value, cas) {
+                Ok(()) => {
+                    if previous.is_none() {
+                        self.index(host, &key);
+                    }
+                    return Some(Observation { record, emit });
+                }
+                Err(Status::CasMismatch) => continue,
+                Err(status) => {
+                    warn!("Failed to update {}: {:?}", key, status);
+                    return None;
+                }
+            }
+        }
+        warn!("Gave up updating {} after concurrent modifications", key);
+        None
+    }
+
+    fn index(&self, host: &impl Host, key: &str) {
+        for _ in 0..MAX_CAS_ATTEMPTS {
+            let (raw, cas) = host.versioned_shared_data(EDGE_INDEX_KEY);
+            let mut keys = raw
+                .and_then(|raw| serde_json::from_slice::<Vec<String>>(&raw).ok())
+                .unwrap_or_default();
+            if keys.iter().any(|known| known == key) {
+                return;
+            }
+            keys.push(key.to_string());
+            let value = serde_json::to_vec(&keys).expect("edge index is serializable");
+            match host.set_versioned_shared_data(EDGE_INDEX_KEY, &value, cas) {
+                Ok(()) => return,
+                Err(Status::CasMismatch) => continue,
+                Err(status) => {
+                    warn!("Failed to update edge index: {:?}", status);
+                    return;
+                }
+            }
+        }
+        warn!("Gave up updating edge index after concurrent modifications");
+    }
+}
+
+/// Every edge the VM has recorded, in the order they were first observed.
+pub fn snapshot(host: &impl Host) -> Vec<ReportedEdge> {
+    let keys = host
+        .shared_data(EDGE_INDEX_KEY)
+        .and_then(|raw| serde_json::from_slice::<Vec<String>>(&raw).ok())
+        .unwrap_or_default();
+    keys.iter()
+        .filter_map(|key| {
+            let raw = host.shared_data(key)?;
+            serde_json::from_slice::<StoredEdge<DependencyEdge>>(&raw)
+                .inspect_err(|err| warn!("Skipping malformed record for {}: {}", key, err))
+                .ok()
+        })
+        .map(|stored| ReportedEdge {
+            edge: stored.edge,
+            first_seen: stored.record.first_seen,
+            last_seen: stored.record.last_seen,
+            requests: None,
+            errors: None,
+            outcomes: None,
+        })
+        .collect()
+}
+
+/// Fields of an edge its key is made of.
+#[derive(Serialize)]
+struct EdgeKey<'a> {
+    principal: Option<&'a str>,
+    namespace: Option<&'a str>,
+    service_account: Option<&'a str>,
+    cluster: Option<&'a str>,
+}
+
+/// Key of the edge: its downstream identity and upstream cluster. The
+/// namespace and service account are part of it as identities need not have a
+/// principal, e.g. those taken from peer metadata.
+fn edge_key(edge: &DependencyEdge) -> String {
+    let key = EdgeKey {
+        principal: edge.downstream.principal.as_deref(),
+        namespace: edge.downstream.namespace.as_deref(),
+        service_account: edge.downstream.service_account.as_deref(),
+        cluster: edge.upstream.cluster.as_deref(),
+    };
+    format!(
+        "{}{}",
+        EDGE_KEY_PREFIX,
+        serde_json::to_string(&key).expect("edge key is serializable")
+    )
+}
+
+fn unix_seconds(time: SystemTime) -> u64 {
+    time.duration_since(UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_secs())
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use dependency_edge::{Downstream, IdentitySource, Request, Upstream};
+
+    use super::*;
+    use crate::{harness::FakeHost, DEFAULT_EDGE_TTL};
+
+    #[test]
+    fn keeps_edges_of_principal_less_identities_apart() {
+        let edge = |namespace: &str| DependencyEdge {
+            downstream: Downstream {
+                source: Some(IdentitySource::PeerMetadata),
+                namespace: Some(namespace.to_string()),
+                service_account: Some("default".to_string()),
+                ..Downstream::default()
+            },
+            upstream: Upstream::from_cluster("outbound|80||server.server.svc.cluster.local"),
+            request: Request::default(),
+        };
+        let host = FakeHost::default();
+        let dedup = EdgeDedup::new(DEFAULT_EDGE_TTL);
+        assert!(
+            dedup
+                .observe(&host, &edge("client"), host.now)
+                .unwrap()
+                .emit
+        );
+        assert!(dedup.observe(&host, &edge("batch"), host.now).unwrap().emit);
+        assert!(
+            !dedup
+                .observe(&host, &edge("client"), host.now)
+                .unwrap()
+                .emit
+        );
+        let namespaces: Vec<_> = snapshot(&host)
+            .into_iter()
+            .map(|reported| reported.edge.downstream.namespace.unwrap())
+            .collect();
+        assert_eq!(namespaces, ["client", "batch"]);
+    }
+
+    fn request(cluster: &str, path: &str) -> DependencyEdge {
+        DependencyEdge {
+            downstream: Downstream {
+                principal: Some("spiffe://cluster.local/ns/client/sa/default".to_string()),
+                ..Downstream::default()
+            },
+            upstream: Upstream::from_cluster(cluster),
+            request: Request {
+                path: Some(path.to_string()),
+                ..Request::default()
+            },
+        }
+    }
+
+    #[test]
+    fn keys_edges_on_identity_and_cluster() {
+        let host = FakeHost::default();
+        let dedup = EdgeDedup::new(DEFAULT_EDGE_TTL);
+        let server = "outbound|80||server.server.svc.cluster.local";
+        let other = "outbound|80||other.server.svc.cluster.local";
+        assert!(
+            dedup
+                .observe(&host, &request(server, "/a"), host.now)
+                .unwrap()
+                .emit
+        );
+        assert!(
+            !dedup
+                .observe(&host, &request(server, "/b"), host.now)
+                .unwrap()
+                .emit
+        );
+        assert!(
+            dedup
+                .observe(&host, &request(other, "/a"), host.now)
+                .unwrap()
+                .emit
+        );
+        assert_eq!(snapshot(&host).len(), 2);
+    }
+
+    #[test]
+    fn writes_each_edge_at_most_once_per_second() {
+        let host = FakeHost::default();
+        let dedup = EdgeDedup::new(DEFAULT_EDGE_TTL);
+        let edge = request("outbound|80||server.server.svc.cluster.local", "/");
+        let key = edge_key(&edge);
+        dedup.observe(&host, &edge, host.now).unwrap();
+        let (_, written) = host.versioned_shared_data(&key);
+
+        let observation = dedup.observe(&host, &edge, host.now).unwrap();
+        assert!(!observation.emit);
+        assert_eq!(host.versioned_shared_data(&key).1, written);
+
+        let later = host.now + Duration::from_secs(1);
+        let observation = dedup.observe(&host, &edge, later).unwrap();
+        assert_eq!(observation.record.last_seen, unix_seconds(later));
+        assert_ne!(host.versioned_shared_data(&key).1, written);
+    }
+}
@@ -0,0 +1,93 @@
+use std::{cell::RefCell, rc::Rc};
+
+use dependency_edge::{Outcomes, ReportedEdge};
+use log::debug;
+
+use crate::{
+    aggregate::EdgeObservation,
+    dedup::EdgeDedup,
+    host::Host,
+    logging::EdgeLog,
+    metrics::{self, Counter},
+    sink::EdgeSink,
+    DependencyLearnerConfig,
+};
+
+/// Hands the edges of http and stream contexts over to aggregation, or to
+/// the sink when not aggregating. Either way the root context sends them.
+pub struct EdgeEmitter {
+    sink: Option<Rc<RefCell<EdgeSink>>>,
+    queue_id: Option<u32>,
+    dedup: EdgeDedup,
+    /// Whether edges are recorded in shared data without a sink, for the
+    /// admin endpoint.
+    record_edges: bool,
+    /// Level edges are logged at, if they are.
+    log_edges: Option<log::Level>,
+    edge_metrics: bool,
+}
+
+impl EdgeEmitter {
+    pub fn new(
+        config: &DependencyLearnerConfig,
+        sink: Option<Rc<RefCell<EdgeSink>>>,
+        queue_id: Option<u32>,
+    ) -> Self {
+        Self {
+            sink,
+            queue_id,
+            dedup: EdgeDedup::new(config.edge_ttl()),
+            record_edges: config.admin.is_some(),
+            log_edges: config.logging.edge_level(),
+            edge_metrics: config.edge_metrics,
+        }
+    }
+
+    /// Emits a single observation of an edge.
+    pub fn emit(&self, host: &impl Host, observation: EdgeObservation) {
+        let edge = &observation.edge;
+        if self.edge_metrics {
+            metrics::increment_edge(edge);
+        }
+        if let Some(level) = self.log_edges {
+            EdgeLog::new(edge, observation.error, observation.outcome.as_ref()).log(level);
+        }
+        if edge.upstream.cluster.is_none() {
+            metrics::increment(Counter::MissingCluster);
+        }
+        if edge.downstream.conforming == Some(false) {
+            debug!(
+                "Downstream principal {} is not a workload SPIFFE ID",
+                edge.downstream.principal.as_deref().unwrap_or_default()
+            );
+        }
+        if let Some(queue_id) = self.queue_id {
+            observation.enqueue(host, queue_id);
+        } else if self.sink.is_some() || self.record_edges {
+            let EdgeObservation { edge, outcome, .. } = observation;
+            let observation = self.dedup.observe(host, &edge, host.now());
+            let Some(sink) = self.sink.as_ref() else {
+                return;
+            };
+            let mut sink = sink.borrow_mut();
+            match observation {
+                Some(observation) if observation.emit => sink.push(ReportedEdge {
+                    edge,
+                    first_seen: observation.record.first_seen,
+                    last_seen: observation.record.last_seen,
+                    requests: None,
+                    errors: None,
+                    // How the upstream answered the request the edge is
+                    // reported for.
+                    outcomes: outcome.map(|outcome| {
+                        let mut outcomes = Outcomes::default();
+                        outcome.record(&mut outcomes);
+                        outcomes
+                    }),
+                }),
+                Some(_) => metrics::increment(Counter::EdgesDeduplicated),
+                None => {}
+            }
+        }
+    }
+}
@@ -0,0 +1,272 @@
+//! A fake proxy to drive the learner with in tests, without a wasm runtime.
+
+use std::{
+    cell::{Cell, RefCell},
+    collections::HashMap,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use proxy_wasm::types::{MetricType, Status};
+
+use crate::host::{Host, HttpHost};
+
+/// A local response sent by the learner.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LocalResponse {
+    pub status: u32,
+    pub headers: Vec<(String, String)>,
+    pub body: Vec<u8>,
+}
+
+/// A call out to a cluster.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Callout {
+    pub cluster: String,
+    /// `<service>/<method>` of gRPC calls, `:path` of http calls.
+    pub path: String,
+    pub body: Vec<u8>,
+}
+
+/// Serves headers and properties from memory and records what the learner
+/// does to them.
+#[derive(Debug)]
+pub struct FakeHost {
+    pub request_headers: RefCell<HashMap<String, String>>,
+    /// In order, as a header may have several values.
+    pub response_headers: RefCell<Vec<(String, String)>>,
+    /// Properties keyed by their path joined with `.`.
+    pub properties: RefCell<HashMap<String, Vec<u8>>>,
+    /// Shared values and their versions.
+    pub shared_data: RefCell<HashMap<String, (Vec<u8>, u32)>>,
+    /// Messages on shared queues, oldest first.
+    pub queued: RefCell<Vec<(u32, Vec<u8>)>>,
+    pub local_response: RefCell<Option<LocalResponse>>,
+    /// Calls out, in order. Their index is their token.
+    pub callouts: RefCell<Vec<Callout>>,
+    /// Status calls out fail with while set, e.g. for an unknown cluster.
+    pub dispatch_failure: Cell<Option<Status>>,
+    pub now: SystemTime,
+}
+
+impl Default for FakeHost {
+    fn default() -> Self {
+        Self {
+            request_headers: RefCell::default(),
+            response_headers: RefCell::default(),
+            properties: RefCell::default(),
+            shared_data: RefCell::default(),
+            queued: RefCell::default(),
+            local_response: RefCell::default(),
+            callouts: RefCell::default(),
+            dispatch_failure: Cell::default(),
+            now: UNIX_EPOCH + Duration::from_secs(1_700_000_000),
+        }
+    }
+}
+
+impl FakeHost {
+    pub fn with_request_headers(headers: &[(&str, &str)]) -> Self {
+        let host = Self::default();
+        for (name, value) in headers {
+            host.set_request_header(name, Some(value));
+        }
+        host
+    }
+
+    pub fn set_property(&self, path: &str, value: &[u8]) {
+        self.properties
+            .borrow_mut()
+            .insert(path.to_string(), value.to_vec());
+    }
+
+    pub fn set_shared_data(&self, key: &str, value: &[u8]) {
+        self.set_versioned_shared_data(key, value, None).unwrap();
+    }
+
+    /// An mTLS connection from a peer with the given URI SANs.
+    pub fn mtls(&self, uri_sans: &str) {
+        self.set_property("connection.mtls", &[1]);
+        self.set_property("connection.uri_san_peer_certificate", uri_sans.as_bytes());
+    }
+
+    fn call_out(&self, cluster: &str, path: String, body: &[u8]) -> Result<u32, Status> {
+        if let Some(status) = self.dispatch_failure.get() {
+            return Err(status);
+        }
+        let mut callouts = self.callouts.borrow_mut();
+        callouts.push(Callout {
+            cluster: cluster.to_string(),
+            path,
+            body: body.to_vec(),
+        });
+        Ok(callouts.len() as u32 - 1)
+    }
+
+    /// Values of a response header, in order.
+    pub fn response_header_values(&self, name: &str) -> Vec<String> {
+        self.response_headers
+            .borrow()
+            .iter()
+            .filter(|(known, _)| known == name)
+            .map(|(_, value)| value.clone())
+            .collect()
+    }
+}
+
+impl Host for FakeHost {
+    fn property(&self, path: &[&str]) -> Option<Vec<u8>> {
+        self.properties.borrow().get(&path.join(".")).cloned()
+    }
+
+    fn versioned_shared_data(&self, key: &str) -> (Option<Vec<u8>>, Option<u32>) {
+        match self.shared_data.borrow().get(key) {
+            Some((value, cas)) => (Some(value.clone()), Some(*cas)),
+            None => (None, None),
+        }
+    }
+
+    fn set_versioned_shared_data(
+        &self,
+        key: &str,
+        value: &[u8],
+        cas: Option<u32>,
+    ) -> Result<(), Status> {
+        let mut shared_data = self.shared_data.borrow_mut();
+        let current = shared_data.get(key).map_or(0, |(_, cas)| *cas);
+        if cas.is_some_and(|cas| cas != current) {
+            return Err(Status::CasMismatch);
+        }
+        shared_data.insert(key.to_string(), (value.to_vec(), current + 1));
+        Ok(())
+    }
+
+    fn enqueue_shared_queue(&self, queue_id: u32, value: &[u8]) -> Result<(), Status> {
+        self.queued.borrow_mut().push((queue_id, value.to_vec()));
+        Ok(())
+    }
+
+    fn dequeue_shared_queue(&self, queue_id: u32) -> Result<Option<Vec<u8>>, Status> {
+        let mut queued = self.queued.borrow_mut();
+        Ok(queued
+            .iter()
+            .position(|(id, _)| *id == queue_id)
+            .map(|i| queued.remove(i).1))
+    }
+
+    fn now(&self) -> SystemTime {
+        self.now
+    }
+
+    fn dispatch_http_call(
+        &self,
+        cluster: &str,
+        headers: Vec<(&str, &str)>,
+        body: &[u8],
+        _: Duration,
+    ) -> Result<u32, Status> {
+        let path = headers
+            .into_iter()
+            .find(|(name, _)| *name == ":path")
+            .map(|(_, path)| path.to_string())
+            .unwrap_or_default();
+        self.call_out(cluster, path, body)
+    }
+
+    fn dispatch_grpc_call(
+        &self,
+        cluster: &str,
+        service: &str,
+        method: &str,
+        message: &[u8],
+        _: Duration,
+    ) -> Result<u32, Status> {
+        self.call_out(cluster, format!("{}/{}", service, method), message)
+    }
+}
+
+impl HttpHost for FakeHost {
+    fn request_header(&self, name: &str) -> Option<String> {
+        self.request_headers.borrow().get(name).cloned()
+    }
+
+    fn set_request_header(&self, name: &str, value: Option<&str>) {
+        let mut headers = self.request_headers.borrow_mut();
+        match value {
+            Some(value) => headers.insert(name.to_string(), value.to_string()),
+            None => headers.remove(name),
+        };
+    }
+
+    fn response_header(&self, name: &str) -> Option<String> {
+        self.response_header_values(name).into_iter().next()
+    }
+
+    fn set_response_header(&self, name: &str, value: Option<&str>) {
+        let mut headers = self.response_headers.borrow_mut();
+        headers.retain(|(known, _)| known != name);
+        if let Some(value) = value {
+            headers.push((name.to_string(), value.to_string()));
+        }
+    }
+
+    fn add_response_header(&self, name: &str, value: &str) {
+        self.response_headers
+            .borrow_mut()
+            .push((name.to_string(), value.to_string()));
+    }
+
+    fn send_response(&self, status: u32, headers: Vec<(&str, &str)>, body: Option<&[u8]>) {
+        self.local_response.replace(Some(LocalResponse {
+            status,
+            headers: headers
+                .into_iter()
+                .map(|(name, value)| (name.to_string(), value.to_string()))
+                .collect(),
+            body: body.unwrap_or_default().to_vec(),
+        }));
+    }
+}
+
+thread_local! {
+    /// Metrics by ID, as names and values.
+    static METRICS: RefCell<Vec<(String, u64)>> = RefCell::default();
+}
+
+/// Defines metrics in `METRICS` in place of the host. Metrics are recorded
+/// through hostcalls rather than [`Host`], as they are not tied to a stream.
+#[no_mangle]
+unsafe extern "C" fn proxy_define_metric(
+    _metric_type: MetricType,
+    name_data: *const u8,
+    name_size: usize,
+    return_id: *mut u32,
+) -> Status {
+    let name =
+        String::from_utf8(std::slice::from_raw_parts(name_data, name_size).to_vec()).unwrap();
+    *return_id = METRICS.with_borrow_mut(|metrics| {
+        metrics.push((name, 0));
+        metrics.len() as u32 - 1
+    });
+    Status::Ok
+}
+
+/// Updates `METRICS` in place of the host.
+#[no_mangle]
+unsafe extern "C" fn proxy_increment_metric(metric_id: u32, offset: i64) -> Status {
+    METRICS.with_borrow_mut(|metrics| {
+        let value = &mut metrics[metric_id as usize].1;
+        *value = value.checked_add_signed(offset).unwrap();
+    });
+    Status::Ok
+}
+
+/// Value of a metric. Metrics outlive tests, which share threads, so tests
+/// look at how values change.
+pub fn metric(name: &str) -> u64 {
+    METRICS.with_borrow(|metrics| {
+        metrics
+            .iter()
+            .find(|(known, _)| known == name)
+            .map_or(0, |(_, value)| *value)
+    })
+}
@@ -0,0 +1,226 @@
+use std::time::{Duration, SystemTime};
+
+use proxy_wasm::{
+    hostcalls,
+    types::{MapType, Status},
+};
+
+/// What edge inference reads from the proxy. Kept apart from the proxy-wasm
+/// contexts so the learner can be driven by a fake host in tests.
+pub trait Host {
+    fn property(&self, path: &[&str]) -> Option<Vec<u8>>;
+    /// Reads a value shared by the VM's worker threads.
+    fn shared_data(&self, key: &str) -> Option<Vec<u8>> {
+        self.versioned_shared_data(key).0
+    }
+    /// Reads a shared value and its version, for
+    /// [`Host::set_versioned_shared_data`].
+    fn versioned_shared_data(&self, key: &str) -> (Option<Vec<u8>>, Option<u32>);
+    /// Sets a shared value, unless it changed since version `cas` was read.
+    fn set_versioned_shared_data(
+        &self,
+        key: &str,
+        value: &[u8],
+        cas: Option<u32>,
+    ) -> Result<(), Status>;
+    /// Adds a message to a shared queue of the VM.
+    fn enqueue_shared_queue(&self, queue_id: u32, value: &[u8]) -> Result<(), Status>;
+    /// Takes the oldest message off a shared queue of the VM, if any.
+    fn dequeue_shared_queue(&self, queue_id: u32) -> Result<Option<Vec<u8>>, Status>;
+    fn now(&self) -> SystemTime;
+    /// Sends an http request to `cluster`. The response goes to the
+    /// `on_http_call_response` of the context calling out.
+    fn dispatch_http_call(
+        &self,
+        cluster: &str,
+        headers: Vec<(&str, &str)>,
+        body: &[u8],
+        timeout: Duration,
+    ) -> Result<u32, Status>;
+    /// Calls a unary gRPC method on `cluster`. The response goes to the
+    /// `on_grpc_call_response` of the context calling out.
+    fn dispatch_grpc_call(
+        &self,
+        cluster: &str,
+        service: &str,
+        method: &str,
+        message: &[u8],
+        timeout: Duration,
+    ) -> Result<u32, Status>;
+}
+
+/// What edge inference reads and writes on an http stream.
+pub trait HttpHost: Host {
+    fn request_header(&self, name: &str) -> Option<String>;
+    /// Sets a request header, or removes it when `value` is `None`.
+    fn set_request_header(&self, name: &str, value: Option<&str>);
+    fn response_header(&self, name: &str) -> Option<String>;
+    /// Sets a response header, or removes it when `value` is `None`.
+    fn set_response_header(&self, name: &str, value: Option<&str>);
+    fn add_response_header(&self, name: &str, value: &str);
+    /// Answers the request locally instead of forwarding it upstream.
+    fn send_response(&self, status: u32, headers: Vec<(&str, &str)>, body: Option<&[u8]>);
+}
+
+/// A borrowed host, e.g. a fake one tests keep hold of while a context
+/// runs against it.
+impl<T: Host + ?Sized> Host for &T {
+    fn property(&self, path: &[&str]) -> Option<Vec<u8>> {
+        (**self).property(path)
+    }
+
+    fn versioned_shared_data(&self, key: &str) -> (Option<Vec<u8>>, Option<u32>) {
+        (**self).versioned_shared_data(key)
+    }
+
+    fn set_versioned_shared_data(
+        &self,
+        key: &str,
+        value: &[u8],
+        cas: Option<u32>,
+    ) -> Result<(), Status> {
+        (**self).set_versioned_shared_data(key, value, cas)
+    }
+
+    fn enqueue_shared_queue(&self, queue_id: u32, value: &[u8]) -> Result<(), Status> {
+        (**self).enqueue_shared_queue(queue_id, value)
+    }
+
+    fn dequeue_shared_queue(&self, queue_id: u32) -> Result<Option<Vec<u8>>, Status> {
+        (**self).dequeue_shared_queue(queue_id)
+    }
+
+    fn now(&self) -> SystemTime {
+        (**self).now()
+    }
+
+    fn dispatch_http_call(
+        &self,
+        cluster: &str,
+        headers: Vec<(&str, &str)>,
+        body: &[u8],
+        timeout: Duration,
+    ) -> Result<u32, Status> {
+        (**self).dispatch_http_call(cluster, headers, body, timeout)
+    }
+
+    fn dispatch_grpc_call(
+        &self,
+        cluster: &str,
+        service: &str,
+        method: &str,
+        message: &[u8],
+        timeout: Duration,
+    ) -> Result<u32, Status> {
+        (**self).dispatch_grpc_call(cluster, service, method, message, timeout)
+    }
+}
+
+impl<T: HttpHost + ?Sized> HttpHost for &T {
+    fn request_header(&self, name: &str) -> Option<String> {
+        (**self).request_header(name)
+    }
+
+    fn set_request_header(&self, name: &str, value: Option<&str>) {
+        (**self).set_request_header(name, value)
+    }
+
+    fn response_header(&self, name: &str) -> Option<String> {
+        (**self).response_header(name)
+    }
+
+    fn set_response_header(&self, name: &str, value: Option<&str>) {
+        (**self).set_response_header(name, value)
+    }
+
+    fn add_response_header(&self, name: &str, value: &str) {
+        (**self).add_response_header(name, value)
+    }
+
+    fn send_response(&self, status: u32, headers: Vec<(&str, &str)>, body: Option<&[u8]>) {
+        (**self).send_response(status, headers, body)
+    }
+}
+
+/// The proxy the plugin runs in. Like the proxy-wasm SDK's contexts, it
+/// treats statuses other than `Ok` and `NotFound` as fatal.
+#[derive(Debug, Clone, Copy)]
+pub struct Proxy;
+
+impl Host for Proxy {
+    fn property(&self, path: &[&str]) -> Option<Vec<u8>> {
+        hostcalls::get_property(path.to_vec()).unwrap()
+    }
+
+    fn versioned_shared_data(&self, key: &str) -> (Option<Vec<u8>>, Option<u32>) {
+        hostcalls::get_shared_data(key).unwrap()
+    }
+
+    fn set_versioned_shared_data(
+        &self,
+        key: &str,
+        value: &[u8],
+        cas: Option<u32>,
+    ) -> Result<(), Status> {
+        hostcalls::set_shared_data(key, Some(value), cas)
+    }
+
+    fn enqueue_shared_queue(&self, queue_id: u32, value: &[u8]) -> Result<(), Status> {
+        hostcalls::enqueue_shared_queue(queue_id, Some(value))
+    }
+
+    fn dequeue_shared_queue(&self, queue_id: u32) -> Result<Option<Vec<u8>>, Status> {
+        hostcalls::dequeue_shared_queue(queue_id)
+    }
+
+    fn now(&self) -> SystemTime {
+        hostcalls::get_current_time().unwrap()
+    }
+
+    fn dispatch_http_call(
+        &self,
+        cluster: &str,
+        headers: Vec<(&str, &str)>,
+        body: &[u8],
+        timeout: Duration,
+    ) -> Result<u32, Status> {
+        hostcalls::dispatch_http_call(cluster, headers, Some(body), vec![], timeout)
+    }
+
+    fn dispatch_grpc_call(
+        &self,
+        cluster: &str,
+        service: &str,
+        method: &str,
+        message: &[u8],
+        timeout: Duration,
+    ) -> Result<u32, Status> {
+        hostcalls::dispatch_grpc_call(cluster, service, method, vec![], Some(message), timeout)
+    }
+}
+
+impl HttpHost for Proxy {
+    fn request_header(&self, name: &str) -> Option<String> {
+        hostcalls::get_map_value(MapType::HttpRequestHeaders, name).unwrap()
+    }
+
+    fn set_request_header(&self, name: &str, value: Option<&str>) {
+        hostcalls::set_map_value(MapType::HttpRequestHeaders, name, value).unwrap()
+    }
+
+    fn response_header(&self, name: &str) -> Option<String> {
+        hostcalls::get_map_value(MapType::HttpResponseHeaders, name).unwrap()
+    }
+
+    fn set_response_header(&self, name: &str, value: Option<&str>) {
+        hostcalls::set_map_value(MapType::HttpResponseHeaders, name, value).unwrap()
+    }
+
+    fn add_response_header(&self, name: &str, value: &str) {
+        hostcalls::add_map_value(MapType::HttpResponseHeaders, name, value).unwrap()
+    }
+
+    fn send_response(&self, status: u32, headers: Vec<(&str, &str)>, body: Option<&[u8]>) {
+        hostcalls::send_http_response(status, headers, body).unwrap()
+    }
+}
@@ -0,0 +1,149 @@
+use dependency_edge::{Downstream, IdentitySource};
+use log::{debug, warn};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    host::{Host, HttpHost},
+    metrics::{self, Counter},
+    peer_metadata::{PeerMetadata, PEER_METADATA_HEADER},
+    string_property,
+    validate::Validator,
+    xfcc,
+};
+
+fn default_xfcc_header() -> String {
+    "x-forwarded-client-cert".to_string()
+}
+
+fn default_peer_metadata_header() -> String {
+    PEER_METADATA_HEADER.to_string()
+}
+
+/// Where the downstream identity is taken from. Sources other than the peer
+/// certificate are set by the downstream or a hop in front of it, so they
+/// should only be configured where that hop overwrites them.
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case", deny_unknown_fields)]
+pub enum IdentitySourceConfig {
+    /// URI SANs of the peer certificate, if the connection is mTLS.
+    PeerCertificate,
+    /// URI SANs of an element of an `x-forwarded-client-cert` header.
+    Xfcc {
+        #[serde(default = "default_xfcc_header")]
+        header: String,
+        #[serde(default)]
+        element: XfccElementSelector,
+    },
+    /// A header holding the principal, or comma separated URI SANs.
+    Header { name: String },
+    /// Namespace and service account sent through Istio's metadata exchange.
+    PeerMetadata {
+        #[serde(default = "default_peer_metadata_header")]
+        header: String,
+    },
+}
+
+/// Which proxy hop of an `x-forwarded-client-cert` header to trust.
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum XfccElementSelector {
+    /// The client of the first hop, i.e. the original downstream. Only safe
+    /// where the first hop sets the header with `SANITIZE_SET`: with Envoy's
+    /// default `APPEND_FORWARD`, the downstream can forge this element.
+    First,
+    /// The client of the last hop, as appended by the proxy in front of this
+    /// one.
+    #[default]
+    Last,
+}
+
+impl IdentitySourceConfig {
+    pub fn validate(&self, path: &str, validator: &mut Validator) {
+        match self {
+            IdentitySourceConfig::PeerCertificate => {}
+            IdentitySourceConfig::Xfcc { header, .. }
+            | IdentitySourceConfig::PeerMetadata { header } => {
+                validator.header_name(format!("{}.header", path), header);
+            }
+            IdentitySourceConfig::Header { name } => {
+                validator.header_name(format!("{}.name", path), name);
+            }
+        }
+    }
+
+    fn source(&self) -> IdentitySource {
+        match self {
+            IdentitySourceConfig::PeerCertificate => IdentitySource::PeerCertificate,
+            IdentitySourceConfig::Xfcc { .. } => IdentitySource::Xfcc,
+            IdentitySourceConfig::Header { .. } => IdentitySource::Header,
+            IdentitySourceConfig::PeerMetadata { .. } => IdentitySource::PeerMetadata,
+        }
+    }
+
+    fn resolve(&self, host: &impl HttpHost) -> Option<Downstream> {
+        match self {
+            IdentitySourceConfig::PeerCertificate => peer_certificate(host),
+            IdentitySourceConfig::Xfcc { header, element } => {
+                let raw = host.request_header(header)?;
+                let Some(elements) = xfcc::parse(&raw) else {
+                    warn!("Ignoring malformed {} header", header);
+                    return None;
+                };
+                let element = match element {
+                    XfccElementSelector::First => elements.first(),
+                    XfccElementSelector::Last => elements.last(),
+                }?;
+                Some(Downstream::from_principals(element.values("URI")))
+            }
+            IdentitySourceConfig::Header { name } => host
+                .request_header(name)
+                .map(|uri_sans| Downstream::from_uri_sans(&uri_sans)),
+            IdentitySourceConfig::PeerMetadata { header } => {
+                let raw = host.request_header(header)?;
+                let Some(metadata) = PeerMetadata::decode(&raw) else {
+                    warn!("Ignoring malformed {} header", header);
+                    return None;
+                };
+                Some(Downstream {
+                    namespace: metadata.namespace,
+                    service_account: metadata.service_account,
+                    ..Downstream::default()
+                })
+            }
+        }
+        .filter(|downstream| downstream.principal.is_some() || downstream.namespace.is_some())
+    }
+}
+
+/// Takes the identity from the URI SANs of the peer certificate, if the
+/// connection is mTLS.
+pub fn peer_certificate(host: &impl Host) -> Option<Downstream> {
+    let mtls = host
+        .property(&["connection", "mtls"])
+        .is_some_and(|raw| raw.first().is_some_and(|b| *b > 0));
+    if !mtls {
+        metrics::increment(Counter::NonMtls);
+        debug!("connection not mTLS; skipping peer certificate");
+        return None;
+    }
+    let uri_sans = string_property(host, &["connection", "uri_san_peer_certificate"])?;
+    Some(Downstream {
+        source: Some(IdentitySource::PeerCertificate),
+        ..Downstream::from_uri_sans(&uri_sans)
+    })
+}
+
+/// Resolves the downstream identity from the first source that has one.
+pub fn resolve(host: &impl HttpHost, sources: &[IdentitySourceConfig]) -> Option<Downstream> {
+    let downstream = sources.iter().find_map(|source| {
+        source.resolve(host).map(|downstream| Downstream {
+            source: Some(source.source()),
+            ..downstream
+        })
+    });
+    if downstream.is_none() {
+        warn!("no identity source yielded the downstream peer");
+    }
+    downstream
+}
@@ -1,156 +1,1948 @@
+use std::{
+    cell::RefCell,
+    rc::Rc,
+    time::{Duration, SystemTime},
+};
+
+use admin::AdminConfig;
+use aggregate::{AggregationConfig, DrainLease, EdgeObservation};
+use allowlist::{Allowlist, AllowlistConfig, Denial, Mode};
+use debug::DebugConfig;
+use dedup::EdgeDedup;
+use dependency_edge::{DependencyEdge, Downstream, Protocol, ReportedEdge, Request, Upstream};
+use emit::EdgeEmitter;
+use host::{Host, HttpHost, Proxy};
+use identity::IdentitySourceConfig;
 use log::{error, trace, warn};
+use logging::{LoggingConfig, VmConfig};
+use metrics::Counter;
+use outcome::Outcome;
+use path::PathTemplateConfig;
+use peer_metadata::{PeerMetadata, PEER_METADATA_HEADER};
 use proxy_wasm::{
-    traits::{Context, HttpContext, RootContext},
-    types::{Action, ContextType, LogLevel},
+    traits::{Context, HttpContext, RootContext, StreamContext},
+    types::{Action, ContextType},
 };
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
+use signing::SigningConfig;
+use sink::{CollectorConfig, EdgeSink};
+use stream::DependencyLearnerStream;
+use validate::{ConfigError, Validator};
+
+mod admin;
+mod aggregate;
+mod allowlist;
+mod debug;
+mod dedup;
+mod emit;
+#[cfg(test)]
+mod harness;
+mod host;
+mod identity;
+mod logging;
+mod metrics;
+mod otlp;
+mod outcome;
+mod path;
+mod peer_metadata;
+mod signing;
+mod sink;
+mod stream;
+mod validate;
+mod xfcc;
 
 proxy_wasm::main! {{
-    proxy_wasm::set_log_level(LogLevel::Trace);
+    proxy_wasm::set_log_level(LoggingConfig::default().level());
     proxy_wasm::set_root_context(|_| -> Box<dyn RootContext> { Box::new(DependencyLearnerRoot::new()) });
 }}
 
-#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+/// Plugin configuration, i.e. the `pluginConfig` of the WasmPlugin. Its JSON
+/// Schema is published in `schema/plugin-config.schema.json`.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
 struct DependencyLearnerConfig {
+    /// Version of the configuration format. Only `1` is supported.
+    version: u32,
     response_header: Option<String>,
+    /// Removes copies of the response header, and of its signature, that the
+    /// downstream sent on the request or the upstream sent on the response.
+    #[serde(default)]
+    strip_inbound_header: bool,
+    /// Replaces an upstream copy of the response header instead of adding
+    /// another value to it.
+    #[serde(default)]
+    replace_header: bool,
+    /// Signs the response header with an HMAC, in a header of its own.
+    signing: Option<SigningConfig>,
+    /// Only sets the response header on requests carrying a debug trigger.
+    /// Every response gets it when unset.
+    debug: Option<DebugConfig>,
+    collector: Option<CollectorConfig>,
+    /// How long an edge is suppressed after being emitted. Defaults to an hour.
+    edge_ttl_ms: Option<u64>,
+    aggregation: Option<AggregationConfig>,
+    /// How edges are rendered in the response header and in logs.
+    #[serde(default)]
+    edge_format: EdgeFormat,
+    /// How request paths are recorded on edges.
+    #[serde(default)]
+    path_template: PathTemplateConfig,
+    #[serde(default)]
+    mode: Mode,
+    /// Edges allowed in audit and enforce mode.
+    allowlist: Option<AllowlistConfig>,
+    /// Whether the plugin runs as an http or a network filter.
+    #[serde(default)]
+    filter_type: FilterType,
+    /// Sources of the downstream identity, tried in order. Defaults to the
+    /// peer certificate. Network filters only use the peer certificate.
+    identity_sources: Option<Vec<IdentitySourceConfig>>,
+    /// Overrides the logging of the VM configuration.
+    #[serde(default)]
+    logging: LoggingConfig,
+    /// Counts requests on a stat of each edge's own, see [`metrics`]. Off by
+    /// default, as every distinct edge defines a stat Envoy never frees.
+    #[serde(default)]
+    edge_metrics: bool,
+    /// Serves the edges the VM has learned on a reserved path. Http filters
+    /// only.
+    admin: Option<AdminConfig>,
+}
+
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "lowercase")]
+enum FilterType {
+    /// Learns an edge per request, see [`DependencyLearner`].
+    #[default]
+    Http,
+    /// Learns an edge per connection, see [`DependencyLearnerStream`].
+    Network,
+}
+
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "lowercase")]
+enum EdgeFormat {
+    /// [`DependencyEdge`] serialized as JSON.
+    #[default]
+    Json,
+    /// The `<principal> -> <cluster>` form, see [`DependencyEdge::legacy`].
+    Legacy,
+}
+
+impl EdgeFormat {
+    fn render(self, edge: &DependencyEdge) -> String {
+        match self {
+            EdgeFormat::Json => serde_json::to_string(edge).expect("edge is serializable"),
+            EdgeFormat::Legacy => edge.legacy(),
+        }
+    }
+}
+
+/// The configuration format this build understands. Bumped on incompatible
+/// changes.
+const CONFIG_VERSION: u32 = 1;
+const DEFAULT_EDGE_TTL: Duration = Duration::from_secs(60 * 60);
+const DEFAULT_IDENTITY_SOURCES: &[IdentitySourceConfig] = &[IdentitySourceConfig::PeerCertificate];
+
+impl DependencyLearnerConfig {
+    /// Parses and validates a plugin configuration.
+    fn parse(raw: &[u8]) -> Result<Self, Vec<ConfigError>> {
+        let config: Self = validate::parse(raw).map_err(|err| vec![err])?;
+        let errors = config.validate();
+        if errors.is_empty() {
+            Ok(config)
+        } else {
+            Err(errors)
+        }
+    }
+
+    fn validate(&self) -> Vec<ConfigError> {
+        let mut validator = Validator::default();
+        validator.check(
+            "version",
+            self.version == CONFIG_VERSION,
+            format!(
+                "unsupported version {}, expected {}",
+                self.version, CONFIG_VERSION
+            ),
+        );
+        if let Some(response_header) = self.response_header.as_deref() {
+            validator.header_name("response_header", response_header);
+        }
+        let has_header = self.response_header.is_some();
+        for (field, set) in [
+            ("strip_inbound_header", self.strip_inbound_header),
+            ("replace_header", self.replace_header),
+            ("signing", self.signing.is_some()),
+            ("debug", self.debug.is_some()),
+        ] {
+            validator.check(field, has_header || !set, "requires response_header");
+        }
+        if let Some(signing) = self.signing.as_ref() {
+            signing.validate("signing", &mut validator);
+        }
+        if let Some(debug) = self.debug.as_ref() {
+            debug.validate("debug", &mut validator);
+        }
+        if let Some(collector) = self.collector.as_ref() {
+            collector.validate("collector", &mut validator);
+        }
+        if let Some(admin) = self.admin.as_ref() {
+            admin.validate("admin", &mut validator);
+        }
+        self.logging.validate("logging", &mut validator);
+        if let Some(edge_ttl_ms) = self.edge_ttl_ms {
+            validator.duration_ms("edge_ttl_ms", edge_ttl_ms);
+        }
+        if let Some(aggregation) = self.aggregation.as_ref() {
+            aggregation.validate("aggregation", &mut validator);
+        }
+        self.path_template.validate("path_template", &mut validator);
+        validator.check(
+            "allowlist",
+            self.mode == Mode::Learn || self.allowlist.is_some(),
+            "required unless mode is learn",
+        );
+        if let Some(allowlist) = self.allowlist.as_ref() {
+            allowlist.validate("allowlist", &mut validator);
+        }
+        for (i, source) in self.identity_sources().iter().enumerate() {
+            source.validate(&format!("identity_sources[{}]", i), &mut validator);
+        }
+        validator.into_errors()
+    }
+
+    fn edge_ttl(&self) -> Duration {
+        self.edge_ttl_ms
+            .map(Duration::from_millis)
+            .unwrap_or(DEFAULT_EDGE_TTL)
+    }
+
+    /// The response header and, when signing, its signature header.
+    fn edge_headers(&self) -> Vec<String> {
+        let Some(response_header) = self.response_header.as_deref() else {
+            return Vec::new();
+        };
+        let mut headers = vec![response_header.to_string()];
+        if let Some(signing) = self.signing.as_ref() {
+            headers.push(signing.header(response_header));
+        }
+        headers
+    }
+
+    fn identity_sources(&self) -> &[IdentitySourceConfig] {
+        self.identity_sources
+            .as_deref()
+            .unwrap_or(DEFAULT_IDENTITY_SOURCES)
+    }
+}
+
+/// Work done every `interval` on tick.
+#[derive(Debug, Default)]
+struct Schedule {
+    interval: Option<Duration>,
+    next: Option<SystemTime>,
+}
+
+impl Schedule {
+    fn every(interval: Duration) -> Self {
+        Self {
+            interval: Some(interval),
+            next: None,
+        }
+    }
+
+    /// Whether the work is due, scheduling the next run if so.
+    fn due(&mut self, now: SystemTime) -> bool {
+        let Some(interval) = self.interval else {
+            return false;
+        };
+        if self.next.is_some_and(|next| now < next) {
+            return false;
+        }
+        self.next = Some(now + interval);
+        true
+    }
 }
 
 struct DependencyLearnerRoot {
+    vm_config: VmConfig,
     config: DependencyLearnerConfig,
+    sink: Option<Rc<RefCell<EdgeSink>>>,
+    queue_id: Option<u32>,
+    /// Whether this root, rather than that of another worker, drains the
+    /// queue.
+    lease: Option<DrainLease>,
+    allowlist: Option<Rc<RefCell<Allowlist>>>,
+    /// Sends the edges buffered in the sink.
+    send: Schedule,
+    /// Drains the aggregation queue.
+    flush: Schedule,
+    refresh: Schedule,
 }
 
 impl DependencyLearnerRoot {
     pub fn new() -> Self {
         Self {
+            vm_config: VmConfig::default(),
             config: DependencyLearnerConfig::default(),
+            sink: None,
+            queue_id: None,
+            lease: None,
+            allowlist: None,
+            send: Schedule::default(),
+            flush: Schedule::default(),
+            refresh: Schedule::default(),
+        }
+    }
+
+    /// Applies a validated plugin configuration, short of what takes
+    /// hostcalls: the aggregation queue and the allowlist.
+    fn configure(&mut self, config: DependencyLearnerConfig) {
+        self.sink = config
+            .collector
+            .clone()
+            .map(|collector| Rc::new(RefCell::new(EdgeSink::new(collector))));
+        self.send = config
+            .collector
+            .as_ref()
+            .map(|collector| Schedule::every(Duration::from_millis(collector.flush_interval_ms)))
+            .unwrap_or_default();
+        self.flush = config
+            .aggregation
+            .as_ref()
+            .map(|aggregation| {
+                Schedule::every(Duration::from_millis(aggregation.flush_interval_ms))
+            })
+            .unwrap_or_default();
+        self.lease = config.aggregation.as_ref().map(DrainLease::new);
+        self.refresh = config
+            .allowlist
+            .as_ref()
+            .map(|allowlist| Schedule::every(Duration::from_millis(allowlist.refresh_interval_ms)))
+            .unwrap_or_default();
+        self.config = config;
+        self.config.logging = self.config.logging.or(self.vm_config.logging);
+    }
+
+    /// The interval of the most frequent work, or zero when there is none.
+    fn tick_period(&self) -> Duration {
+        [
+            self.send.interval,
+            self.flush.interval,
+            self.refresh.interval,
+        ]
+        .into_iter()
+        .flatten()
+        .min()
+        .unwrap_or(Duration::ZERO)
+    }
+
+    /// Sends the edges buffered in the sink, when due.
+    fn send_edges(&mut self, host: &impl Host, now: SystemTime) {
+        if !self.send.due(now) {
+            return;
+        }
+        if let Some(sink) = self.sink.as_ref() {
+            sink.borrow_mut().flush(host, now);
+        }
+    }
+
+    fn flush_aggregates(&mut self, host: &impl Host, now: SystemTime) {
+        if !self
+            .lease
+            .as_mut()
+            .is_some_and(|lease| lease.acquire(host, now))
+        {
+            return;
+        }
+        let aggregates = self
+            .queue_id
+            .map(|queue_id| aggregate::drain(host, queue_id))
+            .unwrap_or_default();
+        if self.sink.is_none() && self.config.admin.is_none() {
+            return;
+        }
+        let dedup = EdgeDedup::new(self.config.edge_ttl());
+        for (edge, counts) in aggregates {
+            let Some(observation) = dedup.observe(host, &edge, now) else {
+                continue;
+            };
+            if let Some(sink) = self.sink.as_ref() {
+                sink.borrow_mut().push(ReportedEdge {
+                    edge,
+                    first_seen: observation.record.first_seen,
+                    last_seen: observation.record.last_seen,
+                    requests: Some(counts.requests),
+                    errors: Some(counts.errors),
+                    outcomes: counts.outcomes,
+                });
+            }
+        }
+        if let Some(sink) = self.sink.as_ref() {
+            sink.borrow_mut().flush(host, now);
         }
     }
 }
 
-impl Context for DependencyLearnerRoot {}
+impl Context for DependencyLearnerRoot {
+    fn on_http_call_response(&mut self, token_id: u32, _: usize, _: usize, _: usize) {
+        if let Some(sink) = self.sink.as_ref() {
+            let status = http_call_status(self);
+            sink.borrow_mut()
+                .on_response(token_id, status, self.get_current_time());
+        }
+    }
+
+    fn on_grpc_call_response(&mut self, token_id: u32, status_code: u32, _: usize) {
+        if let Some(sink) = self.sink.as_ref() {
+            sink.borrow_mut()
+                .on_grpc_response(token_id, status_code, self.get_current_time());
+        }
+    }
+}
 
 impl RootContext for DependencyLearnerRoot {
     fn on_vm_start(&mut self, _vm_configuration_size: usize) -> bool {
         trace!("Initiating DependencyLearner");
+        if let Some(raw_config) = self.get_vm_configuration() {
+            match validate::parse::<VmConfig>(&raw_config) {
+                Ok(vm_config) => self.vm_config = vm_config,
+                Err(err) => {
+                    error!("Rejecting VM configuration: {}", err);
+                    return false;
+                }
+            }
+        }
+        proxy_wasm::set_log_level(self.vm_config.logging.level());
         true
     }
 
     fn on_configure(&mut self, _plugin_configuration_size: usize) -> bool {
         trace!("Initiating DependencyLearner");
         if let Some(raw_config) = self.get_plugin_configuration() {
-            match serde_json::from_slice::<DependencyLearnerConfig>(&raw_config) {
+            match DependencyLearnerConfig::parse(&raw_config) {
                 Ok(c) => {
-                    self.config = c;
+                    self.configure(c);
+                    proxy_wasm::set_log_level(self.config.logging.level());
                 }
-                Err(err) => {
-                    error!("Failed to parse config: {}", err);
+                Err(errors) => {
+                    error!(
+                        "Rejecting plugin configuration: {}",
+                        validate::describe(&errors)
+                    );
                     return false;
                 }
             }
         }
+
+        self.queue_id = self
+            .config
+            .aggregation
+            .as_ref()
+            .map(|aggregation| self.register_shared_queue(&aggregation.queue));
+        self.allowlist = None;
+        if let Some(allowlist) = self.config.allowlist.as_ref() {
+            let loaded = Rc::new(RefCell::new(Allowlist::new(allowlist)));
+            loaded.borrow_mut().refresh(&Proxy);
+            self.allowlist = Some(loaded);
+        }
+        self.set_tick_period(self.tick_period());
         true
     }
 
+    fn on_tick(&mut self) {
+        let now = self.get_current_time();
+        if self.refresh.due(now) {
+            if let Some(allowlist) = self.allowlist.clone() {
+                allowlist.borrow_mut().refresh(&Proxy);
+            }
+        }
+        if self.flush.due(now) {
+            self.flush_aggregates(&Proxy, now);
+        }
+        self.send_edges(&Proxy, now);
+    }
+
     fn get_type(&self) -> Option<ContextType> {
-        Some(ContextType::HttpContext)
+        match self.config.filter_type {
+            FilterType::Http => Some(ContextType::HttpContext),
+            FilterType::Network => Some(ContextType::StreamContext),
+        }
+    }
+
+    fn create_stream_context(&self, _: u32) -> Option<Box<dyn StreamContext>> {
+        Some(Box::new(DependencyLearnerStream::new(
+            self.config.clone(),
+            self.sink.clone(),
+            self.queue_id,
+        )))
     }
 
     fn create_http_context(&self, _: u32) -> Option<Box<dyn HttpContext>> {
-        Some(Box::new(DependencyLearner::new(self.config.clone())))
+        Some(Box::new(DependencyLearner::new(
+            self.config.clone(),
+            self.sink.clone(),
+            self.queue_id,
+            self.allowlist.clone(),
+        )))
+    }
+}
+
+/// Reads a property that holds a string.
+fn string_property(host: &impl Host, path: &[&str]) -> Option<String> {
+    host.property(path).and_then(|raw| {
+        String::from_utf8(raw)
+            .inspect_err(|err| {
+                metrics::increment(Counter::NonUtf8);
+                warn!("{} is not utf8: {}", path.join("."), err)
+            })
+            .ok()
+    })
+}
+
+/// Reads a property that holds an integer, which the proxy encodes as 8
+/// little-endian bytes. Durations are integers of nanoseconds.
+fn int_property(host: &impl Host, path: &[&str]) -> Option<i64> {
+    let raw = host.property(path)?;
+    match <[u8; 8]>::try_from(raw.as_slice()) {
+        Ok(bytes) => Some(i64::from_le_bytes(bytes)),
+        Err(_) => {
+            warn!("{} is not an integer", path.join("."));
+            None
+        }
     }
 }
 
-struct DependencyLearner {
+/// Reads the `:status` of the http call response currently being handled.
+fn http_call_status(ctx: &dyn Context) -> Option<u32> {
+    ctx.get_http_call_response_header(":status")
+        .and_then(|status| status.parse().ok())
+}
+
+/// Learns an edge per http request, from the proxy, or from the host it is
+/// given in tests.
+struct DependencyLearner<H = Proxy> {
+    host: H,
     notified: bool,
+    method: Option<String>,
     path: Option<String>,
     authority: Option<String>,
     upstream_cluster: Option<String>,
-    downstream_peer_certificate: Option<String>,
+    downstream: Option<Downstream>,
+    /// Whether the response header is set on this request.
+    expose_edge: bool,
+    /// The edge learned on response, emitted once the stream is logged.
+    observation: Option<EdgeObservation>,
     config: DependencyLearnerConfig,
+    emitter: EdgeEmitter,
+    allowlist: Option<Rc<RefCell<Allowlist>>>,
 }
 
 impl DependencyLearner {
-    pub fn new(config: DependencyLearnerConfig) -> Self {
+    pub fn new(
+        config: DependencyLearnerConfig,
+        sink: Option<Rc<RefCell<EdgeSink>>>,
+        queue_id: Option<u32>,
+        allowlist: Option<Rc<RefCell<Allowlist>>>,
+    ) -> Self {
+        Self::with_host(Proxy, config, sink, queue_id, allowlist)
+    }
+}
+
+impl<H: HttpHost + Copy> DependencyLearner<H> {
+    fn with_host(
+        host: H,
+        config: DependencyLearnerConfig,
+        sink: Option<Rc<RefCell<EdgeSink>>>,
+        queue_id: Option<u32>,
+        allowlist: Option<Rc<RefCell<Allowlist>>>,
+    ) -> Self {
         Self {
+            host,
+            emitter: EdgeEmitter::new(&config, sink, queue_id),
+            allowlist,
             notified: false,
+            method: None,
             path: None,
             authority: None,
             upstream_cluster: None,
-            downstream_peer_certificate: None,
+            downstream: None,
+            expose_edge: false,
+            observation: None,
             config,
         }
     }
-}
 
-impl Context for DependencyLearner {}
+    fn edge(&self) -> DependencyEdge {
+        DependencyEdge {
+            downstream: self.downstream.clone().unwrap_or_default(),
+            upstream: self
+                .upstream_cluster
+                .as_deref()
+                .map(Upstream::from_cluster)
+                .unwrap_or_default(),
+            request: Request {
+                protocol: Some(Protocol::Http),
+                method: self.method.clone(),
+                path: self.path.clone(),
+                authority: self.authority.clone(),
+                sni: None,
+            },
+        }
+    }
 
-impl HttpContext for DependencyLearner {
-    fn on_http_request_headers(&mut self, _num_headers: usize, end_of_stream: bool) -> Action {
-        if !end_of_stream {
-            return Action::Continue;
+    /// Sets the response header, and its signature when signing.
+    fn set_edge_header(&self, host: &impl HttpHost, response_header: &str, value: &str) {
+        let mut headers = vec![(response_header.to_string(), value.to_string())];
+        if let Some(signing) = self.config.signing.as_ref() {
+            headers.push((
+                signing.header(response_header),
+                signing.sign(value, host.now()),
+            ));
         }
-        if let Some(authority) = self.get_http_request_header(":authority") {
-            self.authority.replace(authority);
+        for (name, value) in headers {
+            if self.config.replace_header {
+                host.set_response_header(&name, Some(&value));
+            } else {
+                host.add_response_header(&name, &value);
+            }
         }
+    }
 
-        if let Some(path) = self.get_http_request_header(":path") {
-            self.path.replace(path);
+    /// Checks the edge against the allowlist in audit and enforce mode.
+    fn check(&mut self, host: &impl HttpHost) -> Action {
+        let Some(allowlist) = self.config.allowlist.as_ref() else {
+            return Action::Continue;
+        };
+        // The route, and with it the cluster, is picked before http filters
+        // run.
+        self.upstream_cluster = string_property(host, &["xds", "cluster_name"]);
+        let edge = self.edge();
+        let allowed = self
+            .allowlist
+            .as_ref()
+            .is_some_and(|allowlist| allowlist.borrow().allows(&edge));
+        match self.config.mode {
+            Mode::Learn => {}
+            Mode::Audit => {
+                if allowed {
+                    host.set_request_header(&allowlist.audit_header, None);
+                } else {
+                    warn!(
+                        "Undeclared dependency: {}",
+                        self.config.edge_format.render(&edge)
+                    );
+                    host.set_request_header(&allowlist.audit_header, Some("true"));
+                }
+            }
+            Mode::Enforce if !allowed => {
+                warn!(
+                    "Rejecting undeclared dependency: {}",
+                    self.config.edge_format.render(&edge)
+                );
+                let body = serde_json::to_vec(&Denial::new(&edge)).expect("denial is serializable");
+                host.send_response(403, vec![("content-type", "application/json")], Some(&body));
+                return Action::Pause;
+            }
+            Mode::Enforce => {}
         }
-
         Action::Continue
     }
 
-    fn on_http_response_headers(&mut self, _body_size: usize, end_of_stream: bool) -> Action {
-        if self.notified {
-            return Action::Continue;
+    /// Captures the request and resolves the downstream, then checks the
+    /// edge in audit and enforce mode.
+    fn on_request(&mut self, host: &impl HttpHost) -> Action {
+        // Headers are complete whether or not a body follows, so they are
+        // captured here rather than once the request ends.
+        metrics::increment(Counter::RequestsObserved);
+        if self.config.strip_inbound_header {
+            for header in self.config.edge_headers() {
+                host.set_request_header(&header, None);
+            }
+        }
+        if let Some(authority) = host.request_header(":authority") {
+            self.authority.replace(authority);
         }
 
-        if !self
-            .get_property(vec!["connection", "mtls"])
-            .map(|raw| raw.len() == 1 && raw.first().map(|b| *b > 0).unwrap_or(false))
-            .unwrap_or(false)
-        {
-            warn!("connection not mTLS; will not be able to infer downstream peer")
+        if let Some(method) = host.request_header(":method") {
+            self.method.replace(method);
         }
 
-        if let Some(downstream_peer_certificate) = self
-            .get_property(vec!["connection", "uri_san_peer_certificate"])
-            .and_then(|raw| {
-                String::from_utf8(raw)
-                    .inspect_err(|err| {
-                        warn!("connection.uri_san_peer_certificate is not utf8: {}", err)
-                    })
-                    .ok()
-            })
-        {
-            self.downstream_peer_certificate
-                .replace(downstream_peer_certificate);
+        if let Some(path) = host.request_header(":path") {
+            self.path.replace(self.config.path_template.template(&path));
         }
 
-        if let Some(upstream_cluster) =
-            self.get_property(vec!["xds", "cluster_name"])
-                .and_then(|raw| {
-                    String::from_utf8(raw)
-                        .inspect_err(|err| warn!("xds.cluster_name is not utf8: {}", err))
-                        .ok()
-                })
+        self.downstream = identity::resolve(host, self.config.identity_sources());
+        if let Some(metadata) = host
+            .request_header(PEER_METADATA_HEADER)
+            .and_then(|raw| PeerMetadata::decode(&raw))
         {
+            let downstream = self.downstream.get_or_insert_with(Downstream::default);
+            if !metadata.enrich(downstream) {
+                warn!(
+                    "Ignoring peer metadata of namespace {:?} for downstream in {:?}",
+                    metadata.namespace, downstream.namespace
+                );
+            }
+        }
+        if let Some(admin) = self.config.admin.as_ref() {
+            if host
+                .request_header(":path")
+                .is_some_and(|path| admin.matches(&path))
+            {
+                admin.respond(host);
+                // The local response is not an edge.
+                self.notified = true;
+                return Action::Pause;
+            }
+        }
+        let principal = self
+            .downstream
+            .as_ref()
+            .and_then(|downstream| downstream.principal.as_deref());
+        self.expose_edge = self
+            .config
+            .debug
+            .as_ref()
+            .is_none_or(|debug| debug.triggered(host, principal));
+
+        self.check(host)
+    }
+
+    /// Sets the response header once the upstream cluster is known, and
+    /// returns the edge, to be emitted once the stream is logged. Streams
+    /// without a cluster are settled once the response headers end the
+    /// stream.
+    fn on_response(
+        &mut self,
+        host: &impl HttpHost,
+        end_of_stream: bool,
+    ) -> Option<EdgeObservation> {
+        if self.notified {
+            return None;
+        }
+
+        if let Some(upstream_cluster) = string_property(host, &["xds", "cluster_name"]) {
             self.upstream_cluster.replace(upstream_cluster);
         }
 
         if self.upstream_cluster.is_some() || end_of_stream {
-            let edge = format!(
-                "{} -> {}",
-                self.downstream_peer_certificate.as_deref().unwrap_or("?"),
-                self.upstream_cluster.as_deref().unwrap_or("?"),
-            );
-            trace!("Dependency learned: {}", edge,);
-            if let Some(response_header) = self.config.response_header.as_deref() {
-                self.add_http_response_header(response_header, &edge);
+            let edge = self.edge();
+            let rendered = self.config.edge_format.render(&edge);
+            if self.config.strip_inbound_header {
+                for header in self.config.edge_headers() {
+                    host.set_response_header(&header, None);
+                }
             }
+            if let Some(response_header) = self.config.response_header.clone() {
+                if self.expose_edge {
+                    self.set_edge_header(host, &response_header, &rendered);
+                }
+            }
+            let error = host
+                .response_header(":status")
+                .and_then(|status| status.parse::<u32>().ok())
+                .is_none_or(|status| status >= 500);
             self.notified = true;
+            return Some(EdgeObservation {
+                edge,
+                error,
+                outcome: None,
+            });
         }
+        None
+    }
 
+    /// Completes the edge learned on response with how the request ended.
+    /// Requests that ended before a response still make an edge, whose
+    /// upstream cluster is known if routing got that far.
+    fn complete(&mut self, host: &impl Host) -> Option<EdgeObservation> {
+        let mut observation = match self.observation.take() {
+            Some(observation) => observation,
+            None if !self.notified => {
+                if let Some(upstream_cluster) = string_property(host, &["xds", "cluster_name"]) {
+                    self.upstream_cluster.replace(upstream_cluster);
+                }
+                self.notified = true;
+                EdgeObservation {
+                    edge: self.edge(),
+                    error: true,
+                    outcome: None,
+                }
+            }
+            None => return None,
+        };
+        let outcome = Outcome::read(host);
+        if outcome.code.is_some() {
+            observation.error = outcome.is_error();
+        }
+        observation.outcome = Some(outcome);
+        Some(observation)
+    }
+}
+
+impl<H: HttpHost + Copy> Context for DependencyLearner<H> {}
+
+impl<H: HttpHost + Copy> HttpContext for DependencyLearner<H> {
+    fn on_http_request_headers(&mut self, _num_headers: usize, _end_of_stream: bool) -> Action {
+        let host = self.host;
+        self.on_request(&host)
+    }
+
+    fn on_http_response_headers(&mut self, _body_size: usize, end_of_stream: bool) -> Action {
+        let host = self.host;
+        if let Some(observation) = self.on_response(&host, end_of_stream) {
+            self.observation = Some(observation);
+        }
         Action::Continue
     }
+
+    fn on_log(&mut self) {
+        let host = self.host;
+        if let Some(observation) = self.complete(&host) {
+            self.emitter.emit(&host, observation);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use base64::{engine::general_purpose::STANDARD, Engine};
+    use debug::DebugHeader;
+    use dependency_edge::IdentitySource;
+    use identity::XfccElementSelector;
+    use logging::EdgeLog;
+    use prost::Message;
+    use prost_types::{value::Kind, Struct, Value};
+
+    use super::*;
+    use crate::harness::{metric, FakeHost};
+
+    fn request(headers: &[(&str, &str)]) -> (DependencyLearner, FakeHost) {
+        with_config(DependencyLearnerConfig::default(), headers)
+    }
+
+    fn with_config(
+        config: DependencyLearnerConfig,
+        headers: &[(&str, &str)],
+    ) -> (DependencyLearner, FakeHost) {
+        let allowlist = config
+            .allowlist
+            .as_ref()
+            .map(|allowlist| Rc::new(RefCell::new(Allowlist::new(allowlist))));
+        (
+            DependencyLearner::new(config, None, None, allowlist),
+            FakeHost::with_request_headers(headers),
+        )
+    }
+
+    /// A learner driven through its http callbacks.
+    fn callbacks(host: &FakeHost) -> DependencyLearner<&FakeHost> {
+        DependencyLearner::with_host(host, DependencyLearnerConfig::default(), None, None, None)
+    }
+
+    #[test]
+    fn captures_bodyless_request() {
+        let host = FakeHost::with_request_headers(&[
+            (":method", "GET"),
+            (":path", "/users/42?verbose=1"),
+            (":authority", "server.server.svc"),
+        ]);
+        let mut learner = callbacks(&host);
+        assert_eq!(learner.on_http_request_headers(3, true), Action::Continue);
+        assert_eq!(learner.method.as_deref(), Some("GET"));
+        assert_eq!(learner.path.as_deref(), Some("/users/{id}"));
+        assert_eq!(learner.authority.as_deref(), Some("server.server.svc"));
+    }
+
+    #[test]
+    fn captures_request_with_body() {
+        let host = FakeHost::with_request_headers(&[
+            (":method", "POST"),
+            (":path", "/users"),
+            (":authority", "server.server.svc"),
+            ("content-length", "2"),
+        ]);
+        let mut learner = callbacks(&host);
+        assert_eq!(learner.on_http_request_headers(4, false), Action::Continue);
+        // Headers are captured before the body arrives.
+        assert_eq!(learner.method.as_deref(), Some("POST"));
+        assert_eq!(learner.path.as_deref(), Some("/users"));
+        assert_eq!(learner.authority.as_deref(), Some("server.server.svc"));
+        learner.on_http_request_body(2, true);
+        assert_eq!(learner.path.as_deref(), Some("/users"));
+    }
+
+    #[test]
+    fn learns_edge_of_trailers_only_grpc_response() {
+        let host = FakeHost::with_request_headers(&[
+            (":method", "POST"),
+            (":path", "/helloworld.Greeter/SayHello"),
+            (":authority", "server.server.svc:50051"),
+            ("content-type", "application/grpc"),
+        ]);
+        let mut learner = callbacks(&host);
+        learner.on_http_request_headers(4, false);
+        learner.on_http_request_body(5, true);
+        // A trailers-only response, e.g. an error without a message, ends
+        // the stream with its headers.
+        host.set_response_header(":status", Some("200"));
+        host.set_response_header("grpc-status", Some("14"));
+        learner.on_http_response_headers(3, true);
+        let edge = learner.observation.unwrap().edge;
+        assert_eq!(edge.upstream.cluster, None);
+        assert_eq!(edge.request.method.as_deref(), Some("POST"));
+        assert_eq!(
+            edge.request.path.as_deref(),
+            Some("/helloworld.Greeter/SayHello")
+        );
+        assert_eq!(
+            edge.request.authority.as_deref(),
+            Some("server.server.svc:50051")
+        );
+    }
+
+    fn identity_sources(sources: Vec<IdentitySourceConfig>) -> DependencyLearnerConfig {
+        DependencyLearnerConfig {
+            identity_sources: Some(sources),
+            ..DependencyLearnerConfig::default()
+        }
+    }
+
+    #[test]
+    fn takes_identity_from_peer_certificate() {
+        let (mut learner, host) = request(&[]);
+        host.set_property("connection.mtls", &[1]);
+        host.set_property(
+            "connection.uri_san_peer_certificate",
+            b"spiffe://cluster.local/ns/client/sa/default",
+        );
+        learner.on_request(&host);
+        let downstream = learner.downstream.unwrap();
+        assert_eq!(downstream.source, Some(IdentitySource::PeerCertificate));
+        assert_eq!(downstream.namespace.as_deref(), Some("client"));
+        assert_eq!(downstream.service_account.as_deref(), Some("default"));
+    }
+
+    #[test]
+    fn falls_back_to_xfcc_without_mtls() {
+        let (mut learner, host) = with_config(
+            identity_sources(vec![
+                IdentitySourceConfig::PeerCertificate,
+                IdentitySourceConfig::Xfcc {
+                    header: "x-forwarded-client-cert".to_string(),
+                    element: XfccElementSelector::First,
+                },
+            ]),
+            &[(
+                "x-forwarded-client-cert",
+                "By=spiffe://cluster.local/ns/lb/sa/proxy;Subject=\"CN=client,O=\\\"Acme\\\"\";\
+                 URI=spiffe://cluster.local/ns/client/sa/batch,\
+                 By=spiffe://cluster.local/ns/edge/sa/default;URI=spiffe://cluster.local/ns/lb/sa/proxy",
+            )],
+        );
+        host.set_property("connection.mtls", &[0]);
+        learner.on_request(&host);
+        let downstream = learner.downstream.unwrap();
+        assert_eq!(downstream.source, Some(IdentitySource::Xfcc));
+        assert_eq!(
+            downstream.principal.as_deref(),
+            Some("spiffe://cluster.local/ns/client/sa/batch")
+        );
+    }
+
+    #[test]
+    fn takes_last_xfcc_element_by_default() {
+        let config =
+            serde_json::from_str(r#"{"version": 1, "identity_sources": [{"xfcc": {}}]}"#).unwrap();
+        let (mut learner, host) = with_config(
+            config,
+            &[(
+                "x-forwarded-client-cert",
+                "URI=spiffe://cluster.local/ns/admin/sa/forged,\
+                 By=spiffe://cluster.local/ns/edge/sa/default;URI=spiffe://cluster.local/ns/lb/sa/proxy",
+            )],
+        );
+        learner.on_request(&host);
+        assert_eq!(
+            learner.downstream.unwrap().principal.as_deref(),
+            Some("spiffe://cluster.local/ns/lb/sa/proxy")
+        );
+    }
+
+    fn string_value(value: &str) -> Value {
+        Value {
+            kind: Some(Kind::StringValue(value.to_string())),
+        }
+    }
+
+    /// Encodes node metadata the way sidecars send it.
+    fn peer_metadata(fields: &[(&str, &str)], labels: &[(&str, &str)]) -> String {
+        let mut metadata = Struct {
+            fields: fields
+                .iter()
+                .map(|(key, value)| (key.to_string(), string_value(value)))
+                .collect(),
+        };
+        let labels = Struct {
+            fields: labels
+                .iter()
+                .map(|(key, value)| (key.to_string(), string_value(value)))
+                .collect(),
+        };
+        metadata.fields.insert(
+            "LABELS".to_string(),
+            Value {
+                kind: Some(Kind::StructValue(labels)),
+            },
+        );
+        STANDARD.encode(metadata.encode_to_vec())
+    }
+
+    #[test]
+    fn takes_identity_from_peer_metadata() {
+        let header = peer_metadata(
+            &[("NAMESPACE", "client"), ("SERVICE_ACCOUNT", "batch")],
+            &[],
+        );
+        let (mut learner, host) = with_config(
+            identity_sources(vec![IdentitySourceConfig::PeerMetadata {
+                header: "x-envoy-peer-metadata".to_string(),
+            }]),
+            &[("x-envoy-peer-metadata", &header)],
+        );
+        learner.on_request(&host);
+        let downstream = learner.downstream.unwrap();
+        assert_eq!(downstream.source, Some(IdentitySource::PeerMetadata));
+        assert_eq!(downstream.principal, None);
+        assert_eq!(downstream.namespace.as_deref(), Some("client"));
+        assert_eq!(downstream.service_account.as_deref(), Some("batch"));
+    }
+
+    #[test]
+    fn adds_workload_from_peer_metadata() {
+        let header = peer_metadata(
+            &[
+                ("NAMESPACE", "client"),
+                ("WORKLOAD_NAME", "client-v2"),
+                ("CLUSTER_ID", "east"),
+            ],
+            &[("app.kubernetes.io/name", "client"), ("version", "v2")],
+        );
+        let (mut learner, host) = request(&[("x-envoy-peer-metadata", &header)]);
+        host.set_property("connection.mtls", &[1]);
+        host.set_property(
+            "connection.uri_san_peer_certificate",
+            b"spiffe://cluster.local/ns/client/sa/default",
+        );
+        learner.on_request(&host);
+        let downstream = learner.downstream.unwrap();
+        assert_eq!(downstream.source, Some(IdentitySource::PeerCertificate));
+        assert_eq!(downstream.service_account.as_deref(), Some("default"));
+        assert_eq!(downstream.workload.as_deref(), Some("client-v2"));
+        assert_eq!(downstream.app.as_deref(), Some("client"));
+        assert_eq!(downstream.version.as_deref(), Some("v2"));
+        assert_eq!(downstream.cluster_id.as_deref(), Some("east"));
+    }
+
+    #[test]
+    fn ignores_peer_metadata_of_other_namespace() {
+        let header = peer_metadata(&[("NAMESPACE", "server"), ("WORKLOAD_NAME", "server")], &[]);
+        let (mut learner, host) = request(&[("x-envoy-peer-metadata", &header)]);
+        host.set_property("connection.mtls", &[1]);
+        host.set_property(
+            "connection.uri_san_peer_certificate",
+            b"spiffe://cluster.local/ns/client/sa/default",
+        );
+        learner.on_request(&host);
+        assert_eq!(learner.downstream.unwrap().workload, None);
+    }
+
+    #[test]
+    fn records_connection_on_new_stream() {
+        let host = FakeHost::default();
+        host.mtls("spiffe://cluster.local/ns/client/sa/default");
+        host.set_property("connection.requested_server_name", b"db.example.com");
+        host.set_property("xds.cluster_name", b"outbound|5432||db.example.com");
+        let mut stream =
+            DependencyLearnerStream::new(DependencyLearnerConfig::default(), None, None);
+        stream.on_connection(&host);
+        assert_eq!(stream.sni.as_deref(), Some("db.example.com"));
+        assert_eq!(
+            stream.upstream_cluster.as_deref(),
+            Some("outbound|5432||db.example.com")
+        );
+        let downstream = stream.downstream.unwrap();
+        assert_eq!(downstream.source, Some(IdentitySource::PeerCertificate));
+        assert_eq!(downstream.namespace.as_deref(), Some("client"));
+    }
+
+    /// Config allowing `client` to call the `server` service only.
+    fn guarded(mode: Mode) -> DependencyLearnerConfig {
+        let allowlist = serde_json::json!({
+            "edges": [{
+                "downstream": {"namespace": "client"},
+                "upstream": {"service": "server", "namespace": "server"},
+            }],
+        });
+        DependencyLearnerConfig {
+            mode,
+            allowlist: Some(serde_json::from_value(allowlist).unwrap()),
+            ..DependencyLearnerConfig::default()
+        }
+    }
+
+    fn from_client(host: &FakeHost, upstream_cluster: &str) {
+        host.mtls("spiffe://cluster.local/ns/client/sa/default");
+        host.set_property("xds.cluster_name", upstream_cluster.as_bytes());
+    }
+
+    #[test]
+    fn enforce_rejects_undeclared_edge() {
+        let (mut learner, host) = with_config(guarded(Mode::Enforce), &[(":path", "/")]);
+        from_client(&host, "outbound|80||other.server.svc.cluster.local");
+        assert!(matches!(learner.on_request(&host), Action::Pause));
+        let response = host.local_response.take().unwrap();
+        assert_eq!(response.status, 403);
+        let body: serde_json::Value = serde_json::from_slice(&response.body).unwrap();
+        assert_eq!(body["error"], "undeclared_dependency");
+        assert_eq!(
+            body["edge"]["upstream"]["host"],
+            "other.server.svc.cluster.local"
+        );
+    }
+
+    #[test]
+    fn enforce_passes_declared_edge() {
+        let (mut learner, host) = with_config(guarded(Mode::Enforce), &[(":path", "/")]);
+        from_client(&host, "outbound|80||server.server.svc.cluster.local");
+        assert!(matches!(learner.on_request(&host), Action::Continue));
+        assert_eq!(host.local_response.take(), None);
+    }
+
+    #[test]
+    fn audit_tags_undeclared_edge() {
+        let (mut learner, host) = with_config(guarded(Mode::Audit), &[]);
+        from_client(&host, "outbound|80||other.server.svc.cluster.local");
+        assert!(matches!(learner.on_request(&host), Action::Continue));
+        assert_eq!(host.local_response.take(), None);
+        assert_eq!(
+            host.request_header("x-dependency-learner-undeclared")
+                .as_deref(),
+            Some("true")
+        );
+    }
+
+    #[test]
+    fn audit_strips_tag_from_declared_edge() {
+        let (mut learner, host) = with_config(
+            guarded(Mode::Audit),
+            &[("x-dependency-learner-undeclared", "true")],
+        );
+        from_client(&host, "outbound|80||server.server.svc.cluster.local");
+        learner.on_request(&host);
+        assert_eq!(host.request_header("x-dependency-learner-undeclared"), None);
+    }
+
+    #[test]
+    fn allows_everything_a_namespace_calls() {
+        let raw = r#"{
+            "version": 1,
+            "mode": "enforce",
+            "allowlist": {"edges": [{"downstream": {"namespace": "client"}}]}
+        }"#;
+        let config = DependencyLearnerConfig::parse(raw.as_bytes()).unwrap();
+        let (mut learner, host) = with_config(config, &[(":path", "/")]);
+        from_client(&host, "outbound|443||api.example.com");
+        assert!(matches!(learner.on_request(&host), Action::Continue));
+    }
+
+    #[test]
+    fn rejects_misspelled_or_empty_allowlist_patterns() {
+        let errors = config_errors(
+            r#"{
+                "version": 1,
+                "mode": "enforce",
+                "allowlist": {"edges": [{"downstream": {"namepsace": "client"}}]}
+            }"#,
+        );
+        assert!(errors[0]
+            .starts_with("allowlist.edges[0].downstream.namepsace: unknown field `namepsace`"));
+        assert_eq!(
+            config_errors(
+                r#"{"version": 1, "mode": "enforce", "allowlist": {"edges": [{"downstream": {}}]}}"#
+            ),
+            ["allowlist.edges[0]: must match at least one field, or it allows every edge"]
+        );
+    }
+
+    #[test]
+    fn strips_spoofed_edge_header_from_request() {
+        let config = DependencyLearnerConfig {
+            response_header: Some("x-dependency-edge".to_string()),
+            strip_inbound_header: true,
+            signing: Some(SigningConfig {
+                key: "secret".to_string(),
+                header: None,
+            }),
+            ..DependencyLearnerConfig::default()
+        };
+        let (mut learner, host) = with_config(
+            config,
+            &[
+                ("x-dependency-edge", "spoofed"),
+                ("x-dependency-edge-signature", "spoofed"),
+            ],
+        );
+        learner.on_request(&host);
+        assert_eq!(host.request_header("x-dependency-edge"), None);
+        assert_eq!(host.request_header("x-dependency-edge-signature"), None);
+    }
+
+    fn debugging(debug: DebugConfig) -> DependencyLearnerConfig {
+        DependencyLearnerConfig {
+            response_header: Some("x-dependency-edge".to_string()),
+            debug: Some(debug),
+            ..DependencyLearnerConfig::default()
+        }
+    }
+
+    #[test]
+    fn exposes_edge_without_debug_config() {
+        let (mut learner, host) = request(&[]);
+        learner.on_request(&host);
+        assert!(learner.expose_edge);
+    }
+
+    #[test]
+    fn exposes_edge_to_debug_header() {
+        let config = debugging(DebugConfig {
+            header: Some(DebugHeader {
+                name: "x-debug".to_string(),
+                value: Some("edges".to_string()),
+            }),
+            ..DebugConfig::default()
+        });
+        let (mut learner, host) = with_config(config.clone(), &[("x-debug", "edges")]);
+        learner.on_request(&host);
+        assert!(learner.expose_edge);
+
+        let (mut learner, host) = with_config(config.clone(), &[("x-debug", "other")]);
+        learner.on_request(&host);
+        assert!(!learner.expose_edge);
+
+        let (mut learner, host) = with_config(config, &[]);
+        learner.on_request(&host);
+        assert!(!learner.expose_edge);
+    }
+
+    #[test]
+    fn exposes_edge_to_debug_principal() {
+        let config = debugging(DebugConfig {
+            principals: vec!["spiffe://cluster.local/ns/debug/sa/default".to_string()],
+            ..DebugConfig::default()
+        });
+        let (mut learner, host) = with_config(config.clone(), &[]);
+        host.set_property("connection.mtls", &[1]);
+        host.set_property(
+            "connection.uri_san_peer_certificate",
+            b"spiffe://cluster.local/ns/debug/sa/default",
+        );
+        learner.on_request(&host);
+        assert!(learner.expose_edge);
+
+        let (mut learner, host) = with_config(config, &[]);
+        host.set_property("connection.mtls", &[1]);
+        host.set_property(
+            "connection.uri_san_peer_certificate",
+            b"spiffe://cluster.local/ns/client/sa/default",
+        );
+        learner.on_request(&host);
+        assert!(!learner.expose_edge);
+    }
+
+    #[test]
+    fn exposes_edge_to_sampled_requests() {
+        let sampled = |sample_percent: f64| {
+            let config = debugging(DebugConfig {
+                sample_percent,
+                ..DebugConfig::default()
+            });
+            (0..100)
+                .filter(|id| {
+                    let request_id = format!("request-{}", id);
+                    let (mut learner, host) =
+                        with_config(config.clone(), &[("x-request-id", &request_id)]);
+                    learner.on_request(&host);
+                    learner.expose_edge
+                })
+                .count()
+        };
+        assert_eq!(sampled(0.0), 0);
+        assert_eq!(sampled(100.0), 100);
+        assert!((10..90).contains(&sampled(50.0)));
+    }
+
+    const ADMIN_PRINCIPAL: &str = "spiffe://cluster.local/ns/ops/sa/debug";
+
+    /// A learner serving the edges to [`ADMIN_PRINCIPAL`], with an edge
+    /// deduplicated in shared data.
+    fn with_admin(
+        mut config: DependencyLearnerConfig,
+        headers: &[(&str, &str)],
+    ) -> (DependencyLearner, FakeHost) {
+        config.admin = Some(AdminConfig {
+            path: "/.well-known/dependency-learner".to_string(),
+            principals: vec![ADMIN_PRINCIPAL.to_string()],
+        });
+        let (learner, host) = with_config(config, headers);
+        host.set_shared_data(
+            "dependency-learner.edges",
+            br#"["dependency-learner.edge.a", "dependency-learner.edge.missing"]"#,
+        );
+        host.set_shared_data(
+            "dependency-learner.edge.a",
+            br#"{"first_seen": 100, "last_seen": 200, "last_emitted": 100, "edge": {"downstream": {"principal": "spiffe://cluster.local/ns/client/sa/default"}, "upstream": {"cluster": "outbound|80||server.server.svc.cluster.local"}}}"#,
+        );
+        (learner, host)
+    }
+
+    fn admin_request(path: &str, principal: &str) -> (DependencyLearner, FakeHost, Action) {
+        let (mut learner, host) = with_admin(
+            DependencyLearnerConfig::default(),
+            &[(":method", "GET"), (":path", path)],
+        );
+        host.mtls(principal);
+        let action = learner.on_request(&host);
+        (learner, host, action)
+    }
+
+    #[test]
+    fn serves_edges_on_admin_path() {
+        let (mut learner, host, action) = admin_request(
+            "/.well-known/dependency-learner?format=json",
+            ADMIN_PRINCIPAL,
+        );
+        assert_eq!(action, Action::Pause);
+        let response = host.local_response.borrow().clone().unwrap();
+        assert_eq!(response.status, 200);
+        let report: dependency_edge::EdgeReport = serde_json::from_slice(&response.body).unwrap();
+        assert_eq!(report.edges.len(), 1);
+        assert_eq!(
+            report.edges[0].edge.legacy(),
+            "spiffe://cluster.local/ns/client/sa/default -> outbound|80||server.server.svc.cluster.local"
+        );
+        assert_eq!(
+            (report.edges[0].first_seen, report.edges[0].last_seen),
+            (100, 200)
+        );
+        assert!(respond(&mut learner, &host, "200").is_none());
+    }
+
+    #[test]
+    fn refuses_edges_to_other_principals() {
+        let (_, host, action) = admin_request(
+            "/.well-known/dependency-learner",
+            "spiffe://cluster.local/ns/client/sa/default",
+        );
+        assert_eq!(action, Action::Pause);
+        let response = host.local_response.borrow().clone().unwrap();
+        assert_eq!(response.status, 403);
+        assert!(!String::from_utf8(response.body)
+            .unwrap()
+            .contains("outbound"));
+    }
+
+    #[test]
+    fn refuses_edges_to_principals_not_from_peer_certificate() {
+        let config = serde_json::from_str(
+            r#"{"version": 1, "identity_sources": [{"xfcc": {}}, {"header": {"name": "x-principal"}}]}"#,
+        )
+        .unwrap();
+        let (mut learner, host) = with_admin(
+            config,
+            &[
+                (":method", "GET"),
+                (":path", "/.well-known/dependency-learner"),
+                (
+                    "x-forwarded-client-cert",
+                    &format!("URI={}", ADMIN_PRINCIPAL),
+                ),
+                ("x-principal", ADMIN_PRINCIPAL),
+            ],
+        );
+        host.mtls("spiffe://cluster.local/ns/client/sa/default");
+        assert_eq!(learner.on_request(&host), Action::Pause);
+        let response = host.local_response.borrow().clone().unwrap();
+        assert_eq!(response.status, 403);
+        assert!(!String::from_utf8(response.body)
+            .unwrap()
+            .contains("outbound"));
+    }
+
+    #[test]
+    fn serves_edges_only_to_get_requests() {
+        let (mut learner, host) = with_admin(
+            DependencyLearnerConfig::default(),
+            &[
+                (":method", "POST"),
+                (":path", "/.well-known/dependency-learner"),
+            ],
+        );
+        host.mtls(ADMIN_PRINCIPAL);
+        assert_eq!(learner.on_request(&host), Action::Pause);
+        let response = host.local_response.borrow().clone().unwrap();
+        assert_eq!(response.status, 405);
+        assert!(response
+            .headers
+            .contains(&("allow".to_string(), "GET".to_string())));
+        assert!(!String::from_utf8(response.body)
+            .unwrap()
+            .contains("outbound"));
+    }
+
+    #[test]
+    fn forwards_other_paths_with_admin_config() {
+        let (_, host, action) =
+            admin_request("/.well-known/dependency-learner/other", ADMIN_PRINCIPAL);
+        assert_eq!(action, Action::Continue);
+        assert!(host.local_response.borrow().is_none());
+        assert_eq!(
+            config_errors(r#"{"version": 1, "admin": {"path": "/edges?all", "principals": []}}"#),
+            [
+                "admin.path: must not have a query",
+                "admin.principals: must allow at least one principal",
+            ]
+        );
+    }
+
+    fn reported(path: &str) -> ReportedEdge {
+        ReportedEdge {
+            edge: DependencyEdge {
+                downstream: Downstream::from_uri_sans(
+                    "spiffe://cluster.local/ns/client/sa/default",
+                ),
+                upstream: Upstream::from_cluster("outbound|80||server.server.svc.cluster.local"),
+                request: Request {
+                    path: Some(path.to_string()),
+                    ..Request::default()
+                },
+            },
+            first_seen: 1_700_000_000,
+            last_seen: 1_700_000_000,
+            requests: None,
+            errors: None,
+            outcomes: None,
+        }
+    }
+
+    #[test]
+    fn sends_partial_batch_on_flush_interval() {
+        let mut root = DependencyLearnerRoot::new();
+        root.configure(
+            serde_json::from_str(r#"{"version": 1, "collector": {"cluster": "collector"}}"#)
+                .unwrap(),
+        );
+        assert_eq!(root.tick_period(), Duration::from_secs(1));
+        let host = FakeHost::default();
+        let sink = root.sink.clone().unwrap();
+
+        sink.borrow_mut().push(reported("/a"));
+        root.send_edges(&host, host.now);
+        let callouts = host.callouts.borrow().clone();
+        assert_eq!(callouts.len(), 1);
+        assert_eq!(
+            (callouts[0].cluster.as_str(), callouts[0].path.as_str()),
+            ("collector", "/edges")
+        );
+        let report: dependency_edge::EdgeReport =
+            serde_json::from_slice(&callouts[0].body).unwrap();
+        assert_eq!(report.edges.len(), 1);
+        assert_eq!(report.edges[0].edge.request.path.as_deref(), Some("/a"));
+
+        sink.borrow_mut().push(reported("/b"));
+        root.send_edges(&host, host.now + Duration::from_millis(500));
+        assert_eq!(host.callouts.borrow().len(), 1);
+        root.send_edges(&host, host.now + Duration::from_secs(1));
+        assert_eq!(host.callouts.borrow().len(), 2);
+    }
+
+    #[test]
+    fn reports_aggregated_counts_per_edge() {
+        let mut root = DependencyLearnerRoot::new();
+        root.configure(
+            serde_json::from_str(
+                r#"{"version": 1, "collector": {"cluster": "collector"}, "aggregation": {}}"#,
+            )
+            .unwrap(),
+        );
+        root.queue_id = Some(7);
+        let host = FakeHost::default();
+        let observed = |path: &str, code: Option<u32>| EdgeObservation {
+            edge: reported(path).edge,
+            error: code.is_none_or(|code| code >= 500),
+            outcome: code.map(|code| Outcome {
+                code: Some(code),
+                duration_ms: Some(10),
+                ..Outcome::default()
+            }),
+        };
+        observed("/a", Some(200)).enqueue(&host, 7);
+        observed("/b", None).enqueue(&host, 7);
+        observed("/a", Some(503)).enqueue(&host, 7);
+        observed("/a", Some(200)).enqueue(&host, 7);
+
+        root.flush_aggregates(&host, host.now);
+        assert!(host.queued.borrow().is_empty());
+        let callouts = host.callouts.borrow().clone();
+        assert_eq!(callouts.len(), 1);
+        let report: dependency_edge::EdgeReport =
+            serde_json::from_slice(&callouts[0].body).unwrap();
+        let [a, b] = report.edges.as_slice() else {
+            panic!("expected an edge per path, got {:?}", report.edges);
+        };
+        assert_eq!(a.edge.request.path.as_deref(), Some("/a"));
+        assert_eq!((a.requests, a.errors), (Some(3), Some(1)));
+        let outcomes = a.outcomes.as_ref().unwrap();
+        assert_eq!(outcomes.statuses, [(200, 2), (503, 1)].into());
+        assert_eq!(outcomes.latency.sum_ms, 30);
+        assert_eq!(b.edge.request.path.as_deref(), Some("/b"));
+        assert_eq!((b.requests, b.errors), (Some(1), Some(1)));
+        assert_eq!(b.outcomes, None);
+    }
+
+    /// Drives a request from `client` to `server`, answered with a 404,
+    /// through the http callbacks until it is logged.
+    fn answer_not_found(learner: &mut DependencyLearner<&FakeHost>, host: &FakeHost) {
+        from_client(host, "outbound|80||server.server.svc.cluster.local");
+        learner.on_http_request_headers(2, true);
+        host.set_response_header(":status", Some("404"));
+        learner.on_http_response_headers(1, true);
+        logged(host, 404, "via_upstream", 42);
+        learner.on_log();
+    }
+
+    #[test]
+    fn reports_outcome_of_edges_without_aggregation() {
+        let config: DependencyLearnerConfig =
+            serde_json::from_str(r#"{"version": 1, "collector": {"cluster": "collector"}}"#)
+                .unwrap();
+        let mut root = DependencyLearnerRoot::new();
+        root.configure(config.clone());
+        let host = FakeHost::with_request_headers(&[(":method", "GET"), (":path", "/")]);
+        let mut learner =
+            DependencyLearner::with_host(&host, config, root.sink.clone(), None, None);
+        answer_not_found(&mut learner, &host);
+
+        root.send_edges(&host, host.now);
+        let callouts = host.callouts.borrow().clone();
+        assert_eq!(callouts.len(), 1);
+        let report: dependency_edge::EdgeReport =
+            serde_json::from_slice(&callouts[0].body).unwrap();
+        assert_eq!(report.edges.len(), 1);
+        let outcomes = report.edges[0].outcomes.as_ref().unwrap();
+        assert_eq!(outcomes.statuses, [(404, 1)].into());
+        assert_eq!(outcomes.details, [("via_upstream".to_string(), 1)].into());
+        assert_eq!(outcomes.latency.sum_ms, 42);
+        assert_eq!(
+            (outcomes.request_bytes, outcomes.response_bytes),
+            (512, 2_048)
+        );
+    }
+
+    #[test]
+    fn counts_requests_per_edge_only_when_enabled() {
+        let name = "dependency_learner.edge\
+            .downstream_namespace.client\
+            .downstream_service_account.default\
+            .upstream_host.server_server_svc_cluster_local\
+            .upstream_port.80\
+            .requests";
+        for (edge_metrics, counted) in [(false, 0), (true, 1)] {
+            let config: DependencyLearnerConfig = serde_json::from_value(
+                serde_json::json!({"version": 1, "edge_metrics": edge_metrics}),
+            )
+            .unwrap();
+            let host = FakeHost::with_request_headers(&[(":method", "GET"), (":path", "/")]);
+            let mut learner = DependencyLearner::with_host(&host, config, None, None, None);
+            let requests = metric(name);
+            answer_not_found(&mut learner, &host);
+            assert_eq!(metric(name), requests + counted);
+        }
+    }
+
+    #[test]
+    fn queues_outcome_of_edges_for_aggregation() {
+        let host = FakeHost::with_request_headers(&[(":method", "GET"), (":path", "/")]);
+        let mut learner = DependencyLearner::with_host(
+            &host,
+            DependencyLearnerConfig::default(),
+            None,
+            Some(7),
+            None,
+        );
+        answer_not_found(&mut learner, &host);
+
+        let queued = host.queued.borrow();
+        assert_eq!(queued.len(), 1);
+        assert_eq!(queued[0].0, 7);
+        let observation: EdgeObservation = serde_json::from_slice(&queued[0].1).unwrap();
+        assert_eq!(observation.outcome.unwrap().code, Some(404));
+    }
+
+    fn config_errors(raw: &str) -> Vec<String> {
+        DependencyLearnerConfig::parse(raw.as_bytes())
+            .map(|_| Vec::new())
+            .unwrap_or_else(|errors| errors.iter().map(ToString::to_string).collect())
+    }
+
+    #[test]
+    fn accepts_valid_config() {
+        let errors = config_errors(
+            r#"{
+                "version": 1,
+                "response_header": "x-dependency-edge",
+                "collector": {"cluster": "outbound|8080||collector.ns.svc.cluster.local"},
+                "identity_sources": [{"xfcc": {}}, {"header": {"name": "x-principal"}}]
+            }"#,
+        );
+        assert_eq!(errors, Vec::<String>::new());
+
+        let errors = config_errors(
+            r#"{
+                "version": 1,
+                "collector": {
+                    "cluster": "outbound|4318||otel-collector.observability.svc.cluster.local",
+                    "transport": "otlp",
+                    "otlp": {"attributes": {"peer.service": "upstream.service"}}
+                }
+            }"#,
+        );
+        assert_eq!(errors, Vec::<String>::new());
+    }
+
+    #[test]
+    fn rejects_unknown_fields_with_path() {
+        let errors = config_errors(r#"{"version": 1, "respons_header": "x-edge"}"#);
+        assert_eq!(errors.len(), 1);
+        assert!(errors[0].starts_with("respons_header: unknown field `respons_header`"));
+
+        let errors = config_errors(
+            r#"{"version": 1, "identity_sources": [{"header": {"name": "x-a", "nme": "x"}}]}"#,
+        );
+        assert!(errors[0].starts_with("identity_sources[0].header.nme: unknown field `nme`"));
+    }
+
+    #[test]
+    fn rejects_missing_or_unsupported_version() {
+        assert!(config_errors("{}")[0].contains("missing field `version`"));
+        assert_eq!(
+            config_errors(r#"{"version": 2}"#),
+            ["version: unsupported version 2, expected 1"]
+        );
+    }
+
+    #[test]
+    fn rejects_invalid_values_with_path() {
+        let errors = config_errors(
+            r#"{
+                "version": 1,
+                "response_header": "X-Edge",
+                "collector": {"cluster": "collector", "path": "edges", "timeout_ms": 0},
+                "debug": {"principals": ["spiffe://"], "sample_percent": 101},
+                "mode": "enforce"
+            }"#,
+        );
+        assert_eq!(
+            errors,
+            [
+                r#"response_header: "X-Edge" is not a lowercase http header name"#,
+                r#"debug.principals[0]: "spiffe://" is not a SPIFFE ID: path is not /ns/<namespace>/sa/<service account>"#,
+                "debug.sample_percent: must be between 0 and 100",
+                r#"collector.path: "edges" is not a path starting with /"#,
+                "collector.timeout_ms: must be a positive number of milliseconds",
+                "allowlist: required unless mode is learn",
+            ]
+        );
+    }
+
+    #[test]
+    fn rejects_edge_logging_at_critical_level() {
+        assert_eq!(
+            config_errors(r#"{"version": 1, "logging": {"level": "critical", "edges": true}}"#),
+            ["logging.edges: edges are not logged at level critical"]
+        );
+        assert_eq!(
+            config_errors(r#"{"version": 1, "logging": {"level": "warn", "edges": true}}"#),
+            Vec::<String>::new()
+        );
+    }
+
+    /// Regenerate with `UPDATE_SCHEMA=1 cargo test`.
+    #[test]
+    fn schema_is_up_to_date() {
+        let path = concat!(
+            env!("CARGO_MANIFEST_DIR"),
+            "/schema/plugin-config.schema.json"
+        );
+        let schema = schemars::schema_for!(DependencyLearnerConfig);
+        let generated = serde_json::to_string_pretty(&schema).unwrap() + "\n";
+        if std::env::var_os("UPDATE_SCHEMA").is_some() {
+            std::fs::write(path, &generated).unwrap();
+        }
+        let published = std::fs::read_to_string(path).unwrap_or_default();
+        assert!(
+            published == generated,
+            "{} is out of date; regenerate it with UPDATE_SCHEMA=1 cargo test",
+            path
+        );
+    }
+
+    fn respond(
+        learner: &mut DependencyLearner,
+        host: &FakeHost,
+        status: &str,
+    ) -> Option<EdgeObservation> {
+        host.set_response_header(":status", Some(status));
+        learner.on_response(host, true)
+    }
+
+    #[test]
+    fn learns_edge_on_response() {
+        let (mut learner, host) = request(&[(":method", "GET"), (":path", "/")]);
+        from_client(&host, "outbound|80||server.server.svc.cluster.local");
+        learner.on_request(&host);
+        host.set_response_header(":status", Some("200"));
+        let observation = learner.on_response(&host, false).unwrap();
+        assert!(!observation.error);
+        let edge = observation.edge;
+        assert_eq!(edge.downstream.namespace.as_deref(), Some("client"));
+        assert_eq!(edge.upstream.service.as_deref(), Some("server"));
+        assert_eq!(edge.request.protocol, Some(Protocol::Http));
+        assert_eq!(edge.request.method.as_deref(), Some("GET"));
+        assert!(learner.on_response(&host, true).is_none());
+    }
+
+    /// What the proxy knows of a request once it is logged.
+    fn logged(host: &FakeHost, code: i64, details: &str, duration_ms: i64) {
+        host.set_property("response.code", &code.to_le_bytes());
+        host.set_property("response.code_details", details.as_bytes());
+        host.set_property("request.duration", &(duration_ms * 1_000_000).to_le_bytes());
+        host.set_property("request.total_size", &512i64.to_le_bytes());
+        host.set_property("response.total_size", &2_048i64.to_le_bytes());
+    }
+
+    #[test]
+    fn emits_edge_with_outcome_on_log() {
+        let (mut learner, host) = request(&[(":method", "GET"), (":path", "/")]);
+        from_client(&host, "outbound|80||server.server.svc.cluster.local");
+        learner.on_request(&host);
+        learner.observation = respond(&mut learner, &host, "404");
+        logged(&host, 404, "via_upstream", 42);
+
+        let observation = learner.complete(&host).unwrap();
+        assert!(!observation.error);
+        assert_eq!(observation.edge.upstream.service.as_deref(), Some("server"));
+        assert_eq!(
+            observation.outcome,
+            Some(Outcome {
+                code: Some(404),
+                code_details: Some("via_upstream".to_string()),
+                duration_ms: Some(42),
+                request_bytes: Some(512),
+                response_bytes: Some(2_048),
+            })
+        );
+        assert!(learner.complete(&host).is_none());
+
+        let line = serde_json::to_value(EdgeLog::new(
+            &observation.edge,
+            observation.error,
+            observation.outcome.as_ref(),
+        ))
+        .unwrap();
+        assert_eq!(line["outcome"]["code"], 404);
+        assert_eq!(line["outcome"]["duration_ms"], 42);
+    }
+
+    #[test]
+    fn emits_failed_edge_for_request_without_response() {
+        let (mut learner, host) = request(&[(":method", "GET"), (":path", "/")]);
+        from_client(&host, "outbound|80||server.server.svc.cluster.local");
+        learner.on_request(&host);
+        logged(&host, 0, "upstream_reset_before_response_started", 3);
+
+        let observation = learner.complete(&host).unwrap();
+        assert!(observation.error);
+        assert_eq!(
+            observation.edge.upstream.cluster.as_deref(),
+            Some("outbound|80||server.server.svc.cluster.local")
+        );
+        let outcome = observation.outcome.unwrap();
+        assert_eq!(outcome.code, None);
+        assert_eq!(
+            outcome.code_details.as_deref(),
+            Some("upstream_reset_before_response_started")
+        );
+
+        let mut outcomes = dependency_edge::Outcomes::default();
+        outcome.record(&mut outcomes);
+        assert_eq!(outcomes.statuses, [(0, 1)].into());
+        assert_eq!(outcomes.latency.counts[0], 1);
+        assert_eq!(outcomes.request_bytes, 512);
+    }
+
+    #[test]
+    fn takes_error_from_logged_response_code() {
+        let (mut learner, host) = request(&[(":path", "/")]);
+        learner.on_request(&host);
+        learner.observation = respond(&mut learner, &host, "200");
+        // e.g. a 200 turned into a 503 by a later filter.
+        logged(&host, 503, "via_upstream", 1);
+        assert!(learner.complete(&host).unwrap().error);
+    }
+
+    #[test]
+    fn emits_no_edge_for_admin_requests() {
+        let (mut learner, host, _) =
+            admin_request("/.well-known/dependency-learner", ADMIN_PRINCIPAL);
+        logged(&host, 200, "direct_response", 0);
+        assert!(learner.complete(&host).is_none());
+    }
+
+    #[test]
+    fn counts_5xx_and_missing_status_as_errors() {
+        let (mut learner, host) = request(&[]);
+        from_client(&host, "outbound|80||server.server.svc.cluster.local");
+        assert!(respond(&mut learner, &host, "503").unwrap().error);
+
+        let (mut learner, host) = request(&[]);
+        from_client(&host, "outbound|80||server.server.svc.cluster.local");
+        assert!(learner.on_response(&host, true).unwrap().error);
+    }
+
+    #[test]
+    fn waits_for_cluster_until_end_of_stream() {
+        let (mut learner, host) = request(&[]);
+        learner.on_request(&host);
+        assert!(learner.on_response(&host, false).is_none());
+        let edge = learner.on_response(&host, true).unwrap().edge;
+        assert_eq!(edge.upstream.cluster, None);
+    }
+
+    #[test]
+    fn skips_non_utf8_cluster_name() {
+        let non_utf8 = metric("dependency_learner.non_utf8");
+        let (mut learner, host) = request(&[]);
+        host.set_property("xds.cluster_name", &[0xff, 0xfe]);
+        learner.on_request(&host);
+        let edge = respond(&mut learner, &host, "200").unwrap().edge;
+        assert_eq!(edge.upstream.cluster, None);
+        assert_eq!(metric("dependency_learner.non_utf8"), non_utf8 + 1);
+    }
+
+    #[test]
+    fn leaves_downstream_unknown_without_san() {
+        let (mut learner, host) = request(&[]);
+        host.set_property("connection.mtls", &[1]);
+        learner.on_request(&host);
+        assert_eq!(learner.downstream, None);
+        let edge = respond(&mut learner, &host, "200").unwrap().edge;
+        assert_eq!(edge.downstream, Downstream::default());
+    }
+
+    #[test]
+    fn sets_edge_header_on_response() {
+        let config = DependencyLearnerConfig {
+            response_header: Some("x-dependency-edge".to_string()),
+            ..DependencyLearnerConfig::default()
+        };
+        let (mut learner, host) = with_config(config, &[]);
+        from_client(&host, "outbound|80||server.server.svc.cluster.local");
+        host.add_response_header("x-dependency-edge", "upstream");
+        learner.on_request(&host);
+        let edge = respond(&mut learner, &host, "200").unwrap().edge;
+        assert_eq!(
+            host.response_header_values("x-dependency-edge"),
+            [
+                "upstream".to_string(),
+                serde_json::to_string(&edge).unwrap()
+            ]
+        );
+    }
+
+    #[test]
+    fn replaces_and_signs_edge_header() {
+        let signing = SigningConfig {
+            key: "secret".to_string(),
+            header: None,
+        };
+        let config = DependencyLearnerConfig {
+            response_header: Some("x-dependency-edge".to_string()),
+            edge_format: EdgeFormat::Legacy,
+            strip_inbound_header: true,
+            replace_header: true,
+            signing: Some(signing.clone()),
+            ..DependencyLearnerConfig::default()
+        };
+        let (mut learner, host) = with_config(config, &[]);
+        from_client(&host, "outbound|80||server.server.svc.cluster.local");
+        host.add_response_header("x-dependency-edge", "upstream");
+        host.add_response_header("x-dependency-edge-signature", "upstream");
+        learner.on_request(&host);
+        respond(&mut learner, &host, "200");
+        let edge = "spiffe://cluster.local/ns/client/sa/default -> \
+            outbound|80||server.server.svc.cluster.local";
+        assert_eq!(host.response_header_values("x-dependency-edge"), [edge]);
+        assert_eq!(
+            host.response_header_values("x-dependency-edge-signature"),
+            [signing.sign(edge, host.now)]
+        );
+    }
+
+    #[test]
+    fn keeps_edge_header_from_untriggered_requests() {
+        let config = debugging(DebugConfig {
+            principals: vec!["spiffe://cluster.local/ns/debug/sa/default".to_string()],
+            ..DebugConfig::default()
+        });
+        let (mut learner, host) = with_config(config, &[]);
+        from_client(&host, "outbound|80||server.server.svc.cluster.local");
+        learner.on_request(&host);
+        assert!(respond(&mut learner, &host, "200").is_some());
+        assert!(host.response_header_values("x-dependency-edge").is_empty());
+    }
+
+    #[test]
+    fn learns_connection_edge_on_close() {
+        let host = FakeHost::default();
+        host.mtls("spiffe://cluster.local/ns/client/sa/default");
+        let mut stream =
+            DependencyLearnerStream::new(DependencyLearnerConfig::default(), None, None);
+        stream.on_connection(&host);
+        assert!(stream.on_event(&host, false).is_none());
+        host.set_property("xds.cluster_name", b"outbound|5432||db.example.com");
+        let observation = stream.on_event(&host, true).unwrap();
+        assert!(observation.error);
+        assert_eq!(observation.edge.request.protocol, Some(Protocol::Tcp));
+        assert_eq!(
+            observation.edge.upstream.host.as_deref(),
+            Some("db.example.com")
+        );
+        assert!(stream.on_event(&host, true).is_none());
+    }
+
+    #[test]
+    fn counts_requests_without_mtls() {
+        let observed = metric("dependency_learner.requests_observed");
+        let non_mtls = metric("dependency_learner.non_mtls");
+        let (mut learner, host) = request(&[]);
+        host.set_property("connection.mtls", &[0]);
+        learner.on_request(&host);
+        assert_eq!(metric("dependency_learner.requests_observed"), observed + 1);
+        assert_eq!(metric("dependency_learner.non_mtls"), non_mtls + 1);
+    }
 }
@@ -0,0 +1,166 @@
+use dependency_edge::DependencyEdge;
+use proxy_wasm::types::LogLevel;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use crate::{outcome::Outcome, validate::Validator};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum Level {
+    Trace,
+    Debug,
+    Info,
+    Warn,
+    Error,
+    Critical,
+}
+
+impl From<Level> for log::Level {
+    fn from(level: Level) -> Self {
+        match level {
+            Level::Trace => log::Level::Trace,
+            Level::Debug => log::Level::Debug,
+            Level::Info => log::Level::Info,
+            Level::Warn => log::Level::Warn,
+            Level::Error | Level::Critical => log::Level::Error,
+        }
+    }
+}
+
+impl From<Level> for LogLevel {
+    fn from(level: Level) -> Self {
+        match level {
+            Level::Trace => LogLevel::Trace,
+            Level::Debug => LogLevel::Debug,
+            Level::Info => LogLevel::Info,
+            Level::Warn => LogLevel::Warn,
+            Level::Error => LogLevel::Error,
+            Level::Critical => LogLevel::Critical,
+        }
+    }
+}
+
+/// Logging, read from the VM configuration and overridden field by field by
+/// the plugin configuration.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct LoggingConfig {
+    /// Defaults to `info`.
+    pub level: Option<Level>,
+    /// Whether every emitted edge is logged as a JSON line. Off by default.
+    /// Edges are logged at `info`, or at `level` when it is stricter, so that
+    /// the level does not silence them. `critical` silences every line, so it
+    /// cannot be combined with edges in the same configuration.
+    pub edges: Option<bool>,
+}
+
+/// VM configuration, shared by every plugin in the VM.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct VmConfig {
+    #[serde(default)]
+    pub logging: LoggingConfig,
+}
+
+impl LoggingConfig {
+    pub const DEFAULT_LEVEL: Level = Level::Info;
+
+    /// `self`, with unset fields taken from `fallback`.
+    pub fn or(self, fallback: LoggingConfig) -> LoggingConfig {
+        LoggingConfig {
+            level: self.level.or(fallback.level),
+            edges: self.edges.or(fallback.edges),
+        }
+    }
+
+    pub fn level(&self) -> LogLevel {
+        self.level.unwrap_or(Self::DEFAULT_LEVEL).into()
+    }
+
+    pub fn validate(&self, path: &str, validator: &mut Validator) {
+        validator.check(
+            format!("{}.edges", path),
+            !(self.edges == Some(true) && self.level == Some(Level::Critical)),
+            "edges are not logged at level critical",
+        );
+    }
+
+    /// Level edges are logged at, if they are.
+    pub fn edge_level(&self) -> Option<log::Level> {
+        let level = log::Level::from(self.level.unwrap_or(Self::DEFAULT_LEVEL));
+        self.edges
+            .unwrap_or_default()
+            .then(|| level.min(log::Level::Info))
+    }
+}
+
+/// A log line per emitted edge, for log shippers to parse.
+#[derive(Debug, Serialize)]
+pub struct EdgeLog<'a> {
+    pub event: &'static str,
+    pub edge: &'a DependencyEdge,
+    pub error: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub outcome: Option<&'a Outcome>,
+}
+
+impl<'a> EdgeLog<'a> {
+    pub fn new(edge: &'a DependencyEdge, error: bool, outcome: Option<&'a Outcome>) -> Self {
+        Self {
+            event: "dependency_learned",
+            edge,
+            error,
+            outcome,
+        }
+    }
+
+    pub fn log(&self, level: log::Level) {
+        log::log!(
+            level,
+            "{}",
+            serde_json::to_string(self).expect("edge log is serializable")
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use dependency_edge::{Downstream, Request, Upstream};
+
+    use super::*;
+    use crate::DependencyLearnerConfig;
+
+    #[test]
+    fn plugin_logging_overrides_vm_logging() {
+        let vm: VmConfig =
+            serde_json::from_str(r#"{"logging": {"level": "warn", "edges": true}}"#).unwrap();
+        let plugin: DependencyLearnerConfig =
+            serde_json::from_str(r#"{"version": 1, "logging": {"level": "debug"}}"#).unwrap();
+        let logging = plugin.logging.or(vm.logging);
+        assert_eq!(logging.level(), LogLevel::Debug);
+        assert_eq!(logging.edge_level(), Some(log::Level::Info));
+        assert_eq!(LoggingConfig::default().level(), LogLevel::Info);
+        assert_eq!(LoggingConfig::default().edge_level(), None);
+    }
+
+    #[test]
+    fn logs_edges_at_levels_stricter_than_info() {
+        let logging: LoggingConfig =
+            serde_json::from_str(r#"{"level": "warn", "edges": true}"#).unwrap();
+        assert_eq!(logging.edge_level(), Some(log::Level::Warn));
+    }
+
+    #[test]
+    fn logs_edges_as_json_lines() {
+        let edge = DependencyEdge {
+            downstream: Downstream::from_uri_sans("spiffe://cluster.local/ns/client/sa/default"),
+            upstream: Upstream::from_cluster("outbound|80||server.server.svc.cluster.local"),
+            request: Request::default(),
+        };
+        let line = serde_json::to_value(EdgeLog::new(&edge, true, None)).unwrap();
+        assert_eq!(line["event"], "dependency_learned");
+        assert_eq!(line["error"], true);
+        assert_eq!(line["edge"], serde_json::to_value(&edge).unwrap());
+    }
+}
@@ -0,0 +1,157 @@
+//! Counters exposed through Envoy's stats, under the `wasmcustom.` prefix
+//! Envoy gives metrics defined by plugins. Istio proxies only export them
+//! with `wasmcustom` in `proxyStatsMatcher.inclusionPrefixes`.
+//!
+//! With `edge_metrics` set, requests are also counted per edge. That counter
+//! encodes its tags in its name, as
+//! `dependency_learner.edge.<tag>.<value>....requests`, so they can be
+//! turned into Prometheus labels with one `stats_tags` entry per tag:
+//!
+//! ```yaml
+//! stats_tags:
+//! - tag_name: downstream_namespace
+//!   regex: '^wasmcustom\.dependency_learner\.edge.*?(\.downstream_namespace\.([^.]*))'
+//! - tag_name: downstream_service_account
+//!   regex: '^wasmcustom\.dependency_learner\.edge.*?(\.downstream_service_account\.([^.]*))'
+//! - tag_name: upstream_host
+//!   regex: '^wasmcustom\.dependency_learner\.edge.*?(\.upstream_host\.([^.]*))'
+//! - tag_name: upstream_port
+//!   regex: '^wasmcustom\.dependency_learner\.edge.*?(\.upstream_port\.([^.]*))'
+//! ```
+//!
+//! Dots in values are replaced by underscores to keep them extractable. Each
+//! distinct edge defines a stat of its own, which Envoy keeps for the life of
+//! the VM, hence the counter being opt-in.
+
+use std::{cell::RefCell, collections::HashMap};
+
+use dependency_edge::DependencyEdge;
+use log::warn;
+use proxy_wasm::{hostcalls, types::MetricType};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Counter {
+    /// Requests, or connections in the network filter, seen by the filter.
+    RequestsObserved,
+    /// Edges handed to the sink.
+    EdgesEmitted,
+    /// Observations not reported as their edge was emitted within the TTL.
+    EdgesDeduplicated,
+    /// Connections whose peer certificate could not be used, as they are not
+    /// mTLS.
+    NonMtls,
+    /// Edges learned without an upstream cluster.
+    MissingCluster,
+    /// Properties that were not valid UTF-8.
+    NonUtf8,
+    /// Edge reports that could not be serialized, dispatched or were not
+    /// accepted.
+    SinkFailures,
+    /// Edges the sink gave up on, as its buffer was full or their report
+    /// failed for good.
+    EdgesDropped,
+}
+
+impl Counter {
+    fn name(self) -> &'static str {
+        match self {
+            Counter::RequestsObserved => "dependency_learner.requests_observed",
+            Counter::EdgesEmitted => "dependency_learner.edges_emitted",
+            Counter::EdgesDeduplicated => "dependency_learner.edges_deduplicated",
+            Counter::NonMtls => "dependency_learner.non_mtls",
+            Counter::MissingCluster => "dependency_learner.missing_cluster",
+            Counter::NonUtf8 => "dependency_learner.non_utf8",
+            Counter::SinkFailures => "dependency_learner.sink_failures",
+            Counter::EdgesDropped => "dependency_learner.edges_dropped",
+        }
+    }
+}
+
+thread_local! {
+    /// IDs of the metrics defined so far, by name.
+    static METRIC_IDS: RefCell<HashMap<String, u32>> = RefCell::default();
+}
+
+pub fn increment(counter: Counter) {
+    add(counter, 1);
+}
+
+pub fn add(counter: Counter, count: u64) {
+    increment_counter(counter.name(), count);
+}
+
+/// Counts a request on the edge's own counter.
+pub fn increment_edge(edge: &DependencyEdge) {
+    let port = edge.upstream.port.map(|port| port.to_string());
+    let tags = [
+        ("downstream_namespace", edge.downstream.namespace.as_deref()),
+        (
+            "downstream_service_account",
+            edge.downstream.service_account.as_deref(),
+        ),
+        (
+            "upstream_host",
+            edge.upstream
+                .host
+                .as_deref()
+                .or(edge.upstream.cluster.as_deref()),
+        ),
+        ("upstream_port", port.as_deref()),
+    ];
+    let mut name = "dependency_learner.edge".to_string();
+    for (tag, value) in tags {
+        name.push('.');
+        name.push_str(tag);
+        name.push('.');
+        name.push_str(&value.unwrap_or("unknown").replace('.', "_"));
+    }
+    name.push_str(".requests");
+    increment_counter(&name, 1);
+}
+
+fn increment_counter(name: &str, count: u64) {
+    let id = METRIC_IDS.with_borrow_mut(|ids| {
+        if let Some(id) = ids.get(name) {
+            return Some(*id);
+        }
+        let id = hostcalls::define_metric(MetricType::Counter, name)
+            .inspect_err(|status| warn!("Failed to define metric {}: {:?}", name, status))
+            .ok()?;
+        ids.insert(name.to_string(), id);
+        Some(id)
+    });
+    if let Some(id) = id {
+        if let Err(status) =
+            hostcalls::increment_metric(id, i64::try_from(count).unwrap_or(i64::MAX))
+        {
+            warn!("Failed to increment metric {}: {:?}", name, status);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use dependency_edge::{Downstream, Request, Upstream};
+
+    use super::*;
+    use crate::harness::metric;
+
+    #[test]
+    fn counts_requests_per_edge() {
+        let edge = DependencyEdge {
+            downstream: Downstream::from_uri_sans("spiffe://cluster.local/ns/client/sa/default"),
+            upstream: Upstream::from_cluster("outbound|80||server.server.svc.cluster.local"),
+            request: Request::default(),
+        };
+        let name = "dependency_learner.edge\
+            .downstream_namespace.client\
+            .downstream_service_account.default\
+            .upstream_host.server_server_svc_cluster_local\
+            .upstream_port.80\
+            .requests";
+        let requests = metric(name);
+        increment_edge(&edge);
+        increment_edge(&edge);
+        assert_eq!(metric(name), requests + 2);
+    }
+}
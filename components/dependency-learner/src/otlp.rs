@@ -0,0 +1,342 @@
+use std::collections::BTreeMap;
+
+use dependency_edge::{DependencyEdge, ReportedEdge};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use crate::validate::Validator;
+
+/// Path OTLP/HTTP collectors accept logs on.
+pub const LOGS_PATH: &str = "/v1/logs";
+
+/// `SeverityNumber` of `INFO` records.
+const SEVERITY_INFO: u8 = 9;
+
+fn default_service_name() -> String {
+    "dependency-learner".to_string()
+}
+
+/// A field of a reported edge an attribute takes its value from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub enum EdgeField {
+    #[serde(rename = "downstream.principal")]
+    DownstreamPrincipal,
+    #[serde(rename = "downstream.trust_domain")]
+    DownstreamTrustDomain,
+    #[serde(rename = "downstream.namespace")]
+    DownstreamNamespace,
+    #[serde(rename = "downstream.service_account")]
+    DownstreamServiceAccount,
+    #[serde(rename = "downstream.workload")]
+    DownstreamWorkload,
+    #[serde(rename = "downstream.app")]
+    DownstreamApp,
+    #[serde(rename = "downstream.version")]
+    DownstreamVersion,
+    #[serde(rename = "downstream.cluster_id")]
+    DownstreamClusterId,
+    #[serde(rename = "upstream.cluster")]
+    UpstreamCluster,
+    #[serde(rename = "upstream.port")]
+    UpstreamPort,
+    #[serde(rename = "upstream.host")]
+    UpstreamHost,
+    #[serde(rename = "upstream.subset")]
+    UpstreamSubset,
+    #[serde(rename = "upstream.service")]
+    UpstreamService,
+    #[serde(rename = "upstream.namespace")]
+    UpstreamNamespace,
+    #[serde(rename = "request.protocol")]
+    RequestProtocol,
+    #[serde(rename = "request.method")]
+    RequestMethod,
+    #[serde(rename = "request.path")]
+    RequestPath,
+    #[serde(rename = "request.authority")]
+    RequestAuthority,
+    #[serde(rename = "request.sni")]
+    RequestSni,
+    #[serde(rename = "requests")]
+    Requests,
+    #[serde(rename = "errors")]
+    Errors,
+}
+
+impl EdgeField {
+    fn value(self, reported: &ReportedEdge) -> Option<AnyValue> {
+        let DependencyEdge {
+            downstream,
+            upstream,
+            request,
+        } = &reported.edge;
+        let string = |value: &Option<String>| value.clone().map(AnyValue::String);
+        match self {
+            Self::DownstreamPrincipal => string(&downstream.principal),
+            Self::DownstreamTrustDomain => string(&downstream.trust_domain),
+            Self::DownstreamNamespace => string(&downstream.namespace),
+            Self::DownstreamServiceAccount => string(&downstream.service_account),
+            Self::DownstreamWorkload => string(&downstream.workload),
+            Self::DownstreamApp => string(&downstream.app),
+            Self::DownstreamVersion => string(&downstream.version),
+            Self::DownstreamClusterId => string(&downstream.cluster_id),
+            Self::UpstreamCluster => string(&upstream.cluster),
+            Self::UpstreamPort => upstream.port.map(|port| AnyValue::int(port.into())),
+            Self::UpstreamHost => string(&upstream.host),
+            Self::UpstreamSubset => string(&upstream.subset),
+            Self::UpstreamService => string(&upstream.service),
+            Self::UpstreamNamespace => string(&upstream.namespace),
+            Self::RequestProtocol => request.protocol.map(|protocol| {
+                AnyValue::String(
+                    match protocol {
+                        dependency_edge::Protocol::Http => "http",
+                        dependency_edge::Protocol::Tcp => "tcp",
+                    }
+                    .to_string(),
+                )
+            }),
+            Self::RequestMethod => string(&request.method),
+            Self::RequestPath => string(&request.path),
+            Self::RequestAuthority => string(&request.authority),
+            Self::RequestSni => string(&request.sni),
+            Self::Requests => reported.requests.map(AnyValue::int),
+            Self::Errors => reported.errors.map(AnyValue::int),
+        }
+    }
+}
+
+/// How edges are exported as OTLP log records.
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct OtlpConfig {
+    /// `service.name` of the exported resource.
+    #[serde(default = "default_service_name")]
+    pub service_name: String,
+    /// Attributes of each record, by name, and the edge fields they take
+    /// their values from. Defaults to OpenTelemetry semantic conventions,
+    /// e.g. `peer.service` from `upstream.service` and `enduser.id` from
+    /// `downstream.principal`. Unknown values are left out.
+    #[serde(default)]
+    pub attributes: Option<BTreeMap<String, EdgeField>>,
+}
+
+impl Default for OtlpConfig {
+    fn default() -> Self {
+        Self {
+            service_name: default_service_name(),
+            attributes: None,
+        }
+    }
+}
+
+impl OtlpConfig {
+    pub fn validate(&self, path: &str, validator: &mut Validator) {
+        validator.check(
+            format!("{}.service_name", path),
+            !self.service_name.is_empty(),
+            "must not be empty",
+        );
+        for name in self.attributes.iter().flat_map(BTreeMap::keys) {
+            validator.check(
+                format!("{}.attributes", path),
+                !name.is_empty(),
+                "attribute names must not be empty",
+            );
+        }
+    }
+
+    fn attributes(&self) -> Vec<(&str, EdgeField)> {
+        match self.attributes.as_ref() {
+            Some(attributes) => attributes
+                .iter()
+                .map(|(name, field)| (name.as_str(), *field))
+                .collect(),
+            None => vec![
+                ("enduser.id", EdgeField::DownstreamPrincipal),
+                ("k8s.namespace.name", EdgeField::DownstreamNamespace),
+                ("k8s.workload.name", EdgeField::DownstreamWorkload),
+                ("peer.service", EdgeField::UpstreamService),
+                ("server.address", EdgeField::UpstreamHost),
+                ("server.port", EdgeField::UpstreamPort),
+                ("network.protocol.name", EdgeField::RequestProtocol),
+                ("http.request.method", EdgeField::RequestMethod),
+                ("url.template", EdgeField::RequestPath),
+            ],
+        }
+    }
+
+    /// An OTLP/HTTP JSON `ExportLogsServiceRequest` with a record per edge,
+    /// timestamped with when it was last seen.
+    pub fn export_logs(&self, edges: &[ReportedEdge]) -> Result<Vec<u8>, serde_json::Error> {
+        let attributes = self.attributes();
+        let log_records = edges
+            .iter()
+            .map(|reported| {
+                let time = (u128::from(reported.last_seen) * 1_000_000_000).to_string();
+                LogRecord {
+                    time_unix_nano: time.clone(),
+                    observed_time_unix_nano: time,
+                    severity_number: SEVERITY_INFO,
+                    severity_text: "INFO",
+                    body: AnyValue::String("dependency_learned".to_string()),
+                    attributes: attributes
+                        .iter()
+                        .filter_map(|(key, field)| {
+                            field.value(reported).map(|value| KeyValue { key, value })
+                        })
+                        .collect(),
+                }
+            })
+            .collect();
+        serde_json::to_vec(&ExportLogsServiceRequest {
+            resource_logs: [ResourceLogs {
+                resource: Resource {
+                    attributes: vec![KeyValue {
+                        key: "service.name",
+                        value: AnyValue::String(self.service_name.clone()),
+                    }],
+                },
+                scope_logs: [ScopeLogs {
+                    scope: Scope {
+                        name: env!("CARGO_PKG_NAME"),
+                        version: env!("CARGO_PKG_VERSION"),
+                    },
+                    log_records,
+                }],
+            }],
+        })
+    }
+}
+
+// The subset of the OTLP/JSON encoding of logs the export needs. 64 bit
+// integers are strings, as the protobuf JSON mapping requires.
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct ExportLogsServiceRequest<'a> {
+    resource_logs: [ResourceLogs<'a>; 1],
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct ResourceLogs<'a> {
+    resource: Resource<'a>,
+    scope_logs: [ScopeLogs<'a>; 1],
+}
+
+#[derive(Serialize)]
+struct Resource<'a> {
+    attributes: Vec<KeyValue<'a>>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct ScopeLogs<'a> {
+    scope: Scope,
+    log_records: Vec<LogRecord<'a>>,
+}
+
+#[derive(Serialize)]
+struct Scope {
+    name: &'static str,
+    version: &'static str,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct LogRecord<'a> {
+    time_unix_nano: String,
+    observed_time_unix_nano: String,
+    severity_number: u8,
+    severity_text: &'static str,
+    body: AnyValue,
+    attributes: Vec<KeyValue<'a>>,
+}
+
+#[derive(Serialize)]
+struct KeyValue<'a> {
+    key: &'a str,
+    value: AnyValue,
+}
+
+#[derive(Serialize)]
+enum AnyValue {
+    #[serde(rename = "stringValue")]
+    String(String),
+    #[serde(rename = "intValue")]
+    Int(String),
+}
+
+impl AnyValue {
+    fn int(value: u64) -> Self {
+        Self::Int(value.to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use dependency_edge::{Downstream, Request, Upstream};
+
+    use super::*;
+
+    fn otlp_record(config: &OtlpConfig) -> serde_json::Value {
+        let edge = ReportedEdge {
+            edge: DependencyEdge {
+                downstream: Downstream::from_uri_sans(
+                    "spiffe://cluster.local/ns/client/sa/default",
+                ),
+                upstream: Upstream::from_cluster("outbound|80||server.server.svc.cluster.local"),
+                request: Request {
+                    method: Some("GET".to_string()),
+                    ..Request::default()
+                },
+            },
+            first_seen: 1_700_000_000,
+            last_seen: 1_700_000_060,
+            requests: Some(3),
+            errors: None,
+            outcomes: None,
+        };
+        let export: serde_json::Value =
+            serde_json::from_slice(&config.export_logs(&[edge]).unwrap()).unwrap();
+        let resource_logs = &export["resourceLogs"][0];
+        assert_eq!(
+            resource_logs["resource"]["attributes"][0],
+            serde_json::json!({"key": "service.name", "value": {"stringValue": "dependency-learner"}})
+        );
+        resource_logs["scopeLogs"][0]["logRecords"][0].clone()
+    }
+
+    #[test]
+    fn exports_edges_as_otlp_logs() {
+        let record = otlp_record(&OtlpConfig::default());
+        assert_eq!(record["timeUnixNano"], "1700000060000000000");
+        assert_eq!(record["body"]["stringValue"], "dependency_learned");
+        assert_eq!(
+            record["attributes"],
+            serde_json::json!([
+                {"key": "enduser.id", "value": {"stringValue": "spiffe://cluster.local/ns/client/sa/default"}},
+                {"key": "k8s.namespace.name", "value": {"stringValue": "client"}},
+                {"key": "peer.service", "value": {"stringValue": "server"}},
+                {"key": "server.address", "value": {"stringValue": "server.server.svc.cluster.local"}},
+                {"key": "server.port", "value": {"intValue": "80"}},
+                {"key": "http.request.method", "value": {"stringValue": "GET"}},
+            ])
+        );
+    }
+
+    #[test]
+    fn maps_otlp_attributes_from_config() {
+        let config: OtlpConfig = serde_json::from_str(
+            r#"{"attributes": {"client.id": "downstream.service_account", "dependency.requests": "requests", "dependency.errors": "errors"}}"#,
+        )
+        .unwrap();
+        assert_eq!(
+            otlp_record(&config)["attributes"],
+            serde_json::json!([
+                {"key": "client.id", "value": {"stringValue": "default"}},
+                {"key": "dependency.requests", "value": {"intValue": "3"}},
+            ])
+        );
+    }
+}
@@ -0,0 +1,61 @@
+use dependency_edge::Outcomes;
+use serde::{Deserialize, Serialize};
+
+use crate::{host::Host, int_property, string_property};
+
+/// How the upstream answered a single request, read once the stream is
+/// logged. Values the proxy does not know, e.g. the status of a request
+/// reset before a response, are `None`.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Outcome {
+    /// Status sent downstream.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub code: Option<u32>,
+    /// Why Envoy answered with `code`, e.g. `via_upstream` or
+    /// `route_not_found`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub code_details: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub duration_ms: Option<u64>,
+    /// Bytes received from the downstream, headers included.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub request_bytes: Option<u64>,
+    /// Bytes sent to the downstream, headers included.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub response_bytes: Option<u64>,
+}
+
+impl Outcome {
+    pub fn read(host: &impl Host) -> Self {
+        let unsigned =
+            |path: &[&str]| int_property(host, path).and_then(|value| u64::try_from(value).ok());
+        Self {
+            // Envoy reports 0 for streams that ended without a response.
+            code: unsigned(&["response", "code"])
+                .and_then(|code| u32::try_from(code).ok())
+                .filter(|code| *code != 0),
+            code_details: string_property(host, &["response", "code_details"]),
+            // A duration, in nanoseconds.
+            duration_ms: unsigned(&["request", "duration"]).map(|ns| ns / 1_000_000),
+            request_bytes: unsigned(&["request", "total_size"]),
+            response_bytes: unsigned(&["response", "total_size"]),
+        }
+    }
+
+    /// Whether the upstream answered with a 5xx or without a status at all.
+    pub fn is_error(&self) -> bool {
+        self.code.is_none_or(|code| code >= 500)
+    }
+
+    pub fn record(&self, outcomes: &mut Outcomes) {
+        *outcomes.statuses.entry(self.code.unwrap_or(0)).or_default() += 1;
+        if let Some(details) = self.code_details.as_ref() {
+            *outcomes.details.entry(details.clone()).or_default() += 1;
+        }
+        if let Some(duration_ms) = self.duration_ms {
+            outcomes.latency.record(duration_ms);
+        }
+        outcomes.request_bytes += self.request_bytes.unwrap_or_default();
+        outcomes.response_bytes += self.response_bytes.unwrap_or_default();
+    }
+}
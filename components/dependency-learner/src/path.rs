@@ -0,0 +1,174 @@
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use crate::validate::Validator;
+
+fn default_rules() -> Vec<SegmentRule> {
+    vec![
+        SegmentRule {
+            matcher: SegmentMatcher::Numeric,
+            replacement: default_replacement(),
+        },
+        SegmentRule {
+            matcher: SegmentMatcher::Uuid,
+            replacement: default_replacement(),
+        },
+    ]
+}
+
+fn default_replacement() -> String {
+    "{id}".to_string()
+}
+
+/// Turns request paths into templates, so requests to different resources
+/// of the same kind collapse into a single edge.
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct PathTemplateConfig {
+    /// Rules applied to each path segment. The first matching rule replaces
+    /// the segment; segments no rule matches are kept.
+    #[serde(default = "default_rules")]
+    pub rules: Vec<SegmentRule>,
+    /// Keep the query string instead of dropping it.
+    #[serde(default)]
+    pub keep_query: bool,
+}
+
+impl Default for PathTemplateConfig {
+    fn default() -> Self {
+        Self {
+            rules: default_rules(),
+            keep_query: false,
+        }
+    }
+}
+
+impl PathTemplateConfig {
+    pub fn validate(&self, path: &str, validator: &mut Validator) {
+        for (i, rule) in self.rules.iter().enumerate() {
+            if let SegmentMatcher::Hex { min_length } = rule.matcher {
+                validator.check(
+                    format!("{}.rules[{}].match.hex.min_length", path, i),
+                    min_length > 0,
+                    "must be positive",
+                );
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct SegmentRule {
+    #[serde(rename = "match")]
+    pub matcher: SegmentMatcher,
+    #[serde(default = "default_replacement")]
+    pub replacement: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case", deny_unknown_fields)]
+pub enum SegmentMatcher {
+    /// Segments made only of ASCII digits.
+    Numeric,
+    /// Segments in the 8-4-4-4-12 hex form of a UUID, in either case.
+    Uuid,
+    /// Segments made only of hex digits, at least `min_length` long, such
+    /// as object IDs and digests.
+    Hex { min_length: usize },
+}
+
+impl SegmentMatcher {
+    fn matches(&self, segment: &str) -> bool {
+        match self {
+            SegmentMatcher::Numeric => {
+                !segment.is_empty() && segment.bytes().all(|b| b.is_ascii_digit())
+            }
+            SegmentMatcher::Uuid => {
+                let groups = segment.split('-').map(str::len).collect::<Vec<_>>();
+                groups == [8, 4, 4, 4, 12]
+                    && segment.bytes().all(|b| b == b'-' || b.is_ascii_hexdigit())
+            }
+            SegmentMatcher::Hex { min_length } => {
+                segment.len() >= (*min_length).max(1)
+                    && segment.bytes().all(|b| b.is_ascii_hexdigit())
+            }
+        }
+    }
+}
+
+impl PathTemplateConfig {
+    pub fn template(&self, path: &str) -> String {
+        let (path, query) = match path.split_once('?') {
+            Some((path, query)) => (path, Some(query)),
+            None => (path, None),
+        };
+        let mut template = path
+            .split('/')
+            .map(|segment| {
+                self.rules
+                    .iter()
+                    .find(|rule| rule.matcher.matches(segment))
+                    .map_or(segment, |rule| rule.replacement.as_str())
+            })
+            .collect::<Vec<_>>()
+            .join("/");
+        if let Some(query) = query.filter(|_| self.keep_query) {
+            template.push('?');
+            template.push_str(query);
+        }
+        template
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config(json: &str) -> PathTemplateConfig {
+        serde_json::from_str(json).unwrap()
+    }
+
+    #[test]
+    fn replaces_uuid_segments() {
+        let config = PathTemplateConfig::default();
+        assert_eq!(
+            config.template("/orders/3F2504E0-4F89-11D3-9A0C-0305E82C3301/items"),
+            "/orders/{id}/items"
+        );
+        assert_eq!(
+            config.template("/orders/3f2504e0-4f89-11d3-9a0c/items"),
+            "/orders/3f2504e0-4f89-11d3-9a0c/items"
+        );
+    }
+
+    #[test]
+    fn replaces_hex_segments_of_at_least_min_length() {
+        let config = config(r#"{"rules": [{"match": {"hex": {"min_length": 8}}}]}"#);
+        assert_eq!(config.template("/blobs/deadbeef00"), "/blobs/{id}");
+        assert_eq!(config.template("/blobs/cafe"), "/blobs/cafe");
+        assert_eq!(config.template("/blobs/deadbeefzz"), "/blobs/deadbeefzz");
+    }
+
+    #[test]
+    fn applies_first_matching_custom_replacement() {
+        let config = config(
+            r#"{"rules": [
+                {"match": "numeric", "replacement": ":number"},
+                {"match": {"hex": {"min_length": 4}}, "replacement": ":digest"}
+            ]}"#,
+        );
+        assert_eq!(
+            config.template("/users/1234/avatars/abcdef"),
+            "/users/:number/avatars/:digest"
+        );
+    }
+
+    #[test]
+    fn keeps_query_only_when_configured() {
+        let path = "/users/42?expand=orders";
+        assert_eq!(PathTemplateConfig::default().template(path), "/users/{id}");
+        let config = config(r#"{"keep_query": true}"#);
+        assert_eq!(config.template(path), "/users/{id}?expand=orders");
+    }
+}
@@ -0,0 +1,248 @@
+//! Istio's metadata exchange, through which sidecars tell their peers about
+//! the workload they belong to.
+
+use std::collections::BTreeMap;
+
+use base64::{engine::general_purpose::STANDARD, Engine};
+use dependency_edge::Downstream;
+use prost::Message;
+use prost_types::{value::Kind, Struct};
+
+pub const PEER_METADATA_HEADER: &str = "x-envoy-peer-metadata";
+
+/// Labels naming the application, in order of preference.
+const APP_LABELS: &[&str] = &[
+    "app",
+    "app.kubernetes.io/name",
+    "service.istio.io/canonical-name",
+];
+/// Labels naming the application version, in order of preference.
+const VERSION_LABELS: &[&str] = &[
+    "version",
+    "app.kubernetes.io/version",
+    "service.istio.io/canonical-revision",
+];
+
+/// The parts of a peer's node metadata edges are keyed on.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PeerMetadata {
+    pub namespace: Option<String>,
+    pub service_account: Option<String>,
+    pub workload: Option<String>,
+    pub labels: BTreeMap<String, String>,
+    pub cluster_id: Option<String>,
+}
+
+impl PeerMetadata {
+    /// Decodes the base64 encoded node metadata sidecars send in the
+    /// `x-envoy-peer-metadata` header: a `google.protobuf.Struct`, or the
+    /// `FlatNode` flatbuffer of older proxies. Returns `None` if it is
+    /// neither.
+    pub fn decode(header: &str) -> Option<Self> {
+        let raw = STANDARD.decode(header.trim()).ok()?;
+        Self::from_protobuf(&raw).or_else(|| Self::from_flatbuffer(&raw))
+    }
+
+    fn from_protobuf(raw: &[u8]) -> Option<Self> {
+        let metadata = Struct::decode(raw).ok()?;
+        if metadata.fields.is_empty() {
+            return None;
+        }
+        let labels = match metadata.fields.get("LABELS").and_then(|v| v.kind.as_ref()) {
+            Some(Kind::StructValue(labels)) => labels
+                .fields
+                .keys()
+                .filter_map(|key| Some((key.clone(), string_field(labels, key)?)))
+                .collect(),
+            _ => BTreeMap::new(),
+        };
+        Some(Self {
+            namespace: string_field(&metadata, "NAMESPACE"),
+            service_account: string_field(&metadata, "SERVICE_ACCOUNT"),
+            workload: string_field(&metadata, "WORKLOAD_NAME"),
+            labels,
+            cluster_id: string_field(&metadata, "CLUSTER_ID"),
+        })
+    }
+
+    /// Reads the fields of Istio's `FlatNode` table:
+    /// `name`, `namespace`, `labels: [KeyVal]`, `owner`, `workload_name`,
+    /// `istio_version`, `mesh_id`, `cluster_id`, ...
+    fn from_flatbuffer(raw: &[u8]) -> Option<Self> {
+        let node = FlatTable::root(raw)?;
+        let mut labels = BTreeMap::new();
+        for label in node.tables(2)? {
+            if let (Some(key), Some(value)) = (label.string(0), label.string(1)) {
+                labels.insert(key.to_string(), value.to_string());
+            }
+        }
+        Some(Self {
+            namespace: node.string(1).map(str::to_string),
+            service_account: None,
+            workload: node.string(4).map(str::to_string),
+            labels,
+            cluster_id: node.string(7).map(str::to_string),
+        })
+    }
+
+    pub fn app(&self) -> Option<&str> {
+        self.label(APP_LABELS)
+    }
+
+    pub fn version(&self) -> Option<&str> {
+        self.label(VERSION_LABELS)
+    }
+
+    fn label(&self, keys: &[&str]) -> Option<&str> {
+        keys.iter()
+            .find_map(|key| self.labels.get(*key))
+            .map(String::as_str)
+    }
+
+    /// Adds the workload level parts to a downstream, unless the metadata
+    /// belongs to another namespace than the downstream's identity.
+    pub fn enrich(&self, downstream: &mut Downstream) -> bool {
+        if let (Some(identity), Some(peer)) = (&downstream.namespace, &self.namespace) {
+            if identity != peer {
+                return false;
+            }
+        }
+        downstream.workload = self.workload.clone();
+        downstream.app = self.app().map(str::to_string);
+        downstream.version = self.version().map(str::to_string);
+        downstream.cluster_id = self.cluster_id.clone();
+        true
+    }
+}
+
+fn string_field(metadata: &Struct, key: &str) -> Option<String> {
+    match metadata.fields.get(key)?.kind.as_ref()? {
+        Kind::StringValue(value) if !value.is_empty() => Some(value.clone()),
+        _ => None,
+    }
+}
+
+/// A bounds checked view of a flatbuffer table.
+#[derive(Clone, Copy)]
+struct FlatTable<'a> {
+    buf: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> FlatTable<'a> {
+    fn root(buf: &'a [u8]) -> Option<Self> {
+        let pos = read_u32(buf, 0)? as usize;
+        Self::at(buf, pos)
+    }
+
+    fn at(buf: &'a [u8], pos: usize) -> Option<Self> {
+        // The table starts with a signed offset back to its vtable, which
+        // has to be readable for the table to be.
+        let table = Self { buf, pos };
+        table.vtable()?;
+        Some(table)
+    }
+
+    fn vtable(&self) -> Option<usize> {
+        let offset = i32::from_le_bytes(
+            self.buf
+                .get(self.pos..self.pos.checked_add(4)?)?
+                .try_into()
+                .ok()?,
+        );
+        let vtable = usize::try_from(self.pos as i64 - offset as i64).ok()?;
+        read_u16(self.buf, vtable)?;
+        Some(vtable)
+    }
+
+    /// Position of a field, if present.
+    fn field(&self, index: usize) -> Option<usize> {
+        let vtable = self.vtable()?;
+        let vtable_size = read_u16(self.buf, vtable)? as usize;
+        let slot = 4 + 2 * index;
+        if slot + 2 > vtable_size {
+            return None;
+        }
+        match read_u16(self.buf, vtable + slot)? {
+            0 => None,
+            offset => Some(self.pos + offset as usize),
+        }
+    }
+
+    /// Follows the offset stored at `pos`.
+    fn indirect(&self, pos: usize) -> Option<usize> {
+        pos.checked_add(read_u32(self.buf, pos)? as usize)
+    }
+
+    fn string(&self, index: usize) -> Option<&'a str> {
+        let pos = self.indirect(self.field(index)?)?;
+        let len = read_u32(self.buf, pos)? as usize;
+        let start = pos.checked_add(4)?;
+        std::str::from_utf8(self.buf.get(start..start.checked_add(len)?)?)
+            .ok()
+            .filter(|value| !value.is_empty())
+    }
+
+    /// Tables of a vector field. An absent field is an empty vector.
+    fn tables(&self, index: usize) -> Option<Vec<FlatTable<'a>>> {
+        let Some(field) = self.field(index) else {
+            return Some(Vec::new());
+        };
+        let pos = self.indirect(field)?;
+        let len = read_u32(self.buf, pos)? as usize;
+        (0..len)
+            .map(|i| {
+                let element = pos + 4 + 4 * i;
+                Self::at(self.buf, self.indirect(element)?)
+            })
+            .collect()
+    }
+}
+
+fn read_u16(buf: &[u8], pos: usize) -> Option<u16> {
+    Some(u16::from_le_bytes(
+        buf.get(pos..pos.checked_add(2)?)?.try_into().ok()?,
+    ))
+}
+
+fn read_u32(buf: &[u8], pos: usize) -> Option<u32> {
+    Some(u32::from_le_bytes(
+        buf.get(pos..pos.checked_add(4)?)?.try_into().ok()?,
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn decodes_flatbuffer_peer_metadata() {
+        let mut builder = flatbuffers::FlatBufferBuilder::new();
+        let labels = [("app", "client"), ("version", "v1")].map(|(key, value)| {
+            let key = builder.create_string(key);
+            let value = builder.create_string(value);
+            let start = builder.start_table();
+            builder.push_slot_always(4, key);
+            builder.push_slot_always(6, value);
+            builder.end_table(start)
+        });
+        let labels = builder.create_vector(&labels);
+        let namespace = builder.create_string("client");
+        let workload = builder.create_string("client-v1");
+        let cluster_id = builder.create_string("west");
+        let start = builder.start_table();
+        builder.push_slot_always(6, namespace);
+        builder.push_slot_always(8, labels);
+        builder.push_slot_always(12, workload);
+        builder.push_slot_always(18, cluster_id);
+        let node = builder.end_table(start);
+        builder.finish_minimal(node);
+
+        let metadata = PeerMetadata::decode(&STANDARD.encode(builder.finished_data())).unwrap();
+        assert_eq!(metadata.namespace.as_deref(), Some("client"));
+        assert_eq!(metadata.workload.as_deref(), Some("client-v1"));
+        assert_eq!(metadata.app(), Some("client"));
+        assert_eq!(metadata.version(), Some("v1"));
+        assert_eq!(metadata.cluster_id.as_deref(), Some("west"));
+    }
+}
@@ -0,0 +1,603 @@
+use std::{
+    cmp,
+    collections::{HashMap, VecDeque},
+    time::{Duration, SystemTime},
+};
+
+use dependency_edge::{
+    proto::{self, EDGE_COLLECTOR_SERVICE, REPORT_METHOD},
+    ReportedEdge,
+};
+use log::{error, trace, warn};
+use prost::Message;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    host::Host,
+    metrics::{self, Counter},
+    otlp::{self, OtlpConfig},
+    validate::Validator,
+};
+
+fn default_timeout_ms() -> u64 {
+    5_000
+}
+
+fn default_batch_size() -> usize {
+    32
+}
+
+fn default_flush_interval_ms() -> u64 {
+    1_000
+}
+
+fn default_max_retries() -> u32 {
+    5
+}
+
+fn default_max_buffered_edges() -> usize {
+    10_000
+}
+
+fn default_initial_backoff_ms() -> u64 {
+    500
+}
+
+fn default_max_backoff_ms() -> u64 {
+    30_000
+}
+
+/// gRPC status codes worth resending a batch after: the collector may
+/// accept it once it recovers. UNKNOWN, DEADLINE_EXCEEDED, RESOURCE_EXHAUSTED,
+/// ABORTED, INTERNAL and UNAVAILABLE.
+const RETRYABLE_GRPC_STATUSES: &[u32] = &[2, 4, 8, 10, 13, 14];
+
+/// How reports are sent to the collector.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum Transport {
+    /// JSON [`dependency_edge::EdgeReport`]s POSTed to `path`.
+    #[default]
+    Http,
+    /// Protobuf `EdgeReport`s sent to `dependency_learner.v1.EdgeCollector`,
+    /// see `dependency-edge/proto/dependency_learner/v1/edges.proto`.
+    Grpc,
+    /// OTLP/HTTP JSON logs POSTed to `path`, a record per edge, see `otlp`.
+    Otlp,
+}
+
+/// Where and how learned edges are shipped.
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct CollectorConfig {
+    /// Envoy cluster the collector is reachable through, e.g.
+    /// `outbound|8080||collector.ns.svc.cluster.local`.
+    pub cluster: String,
+    #[serde(default)]
+    pub transport: Transport,
+    /// `:authority` sent with each http report. Defaults to `cluster`.
+    pub authority: Option<String>,
+    /// Path http reports are POSTed to. Defaults to `/edges`, or `/v1/logs`
+    /// with the `otlp` transport.
+    pub path: Option<String>,
+    #[serde(default = "default_timeout_ms")]
+    pub timeout_ms: u64,
+    /// Maximum number of edges sent in a single report.
+    #[serde(default = "default_batch_size")]
+    pub batch_size: usize,
+    /// How often buffered edges are sent, and failed reports resent.
+    #[serde(default = "default_flush_interval_ms")]
+    pub flush_interval_ms: u64,
+    /// Number of times a failed batch is resent before it is dropped.
+    #[serde(default = "default_max_retries")]
+    pub max_retries: u32,
+    /// Maximum number of edges kept unsent, e.g. while the collector is
+    /// unavailable. Edges beyond it are dropped and counted in
+    /// `dependency_learner.edges_dropped`.
+    #[serde(default = "default_max_buffered_edges")]
+    pub max_buffered_edges: usize,
+    #[serde(default = "default_initial_backoff_ms")]
+    pub initial_backoff_ms: u64,
+    #[serde(default = "default_max_backoff_ms")]
+    pub max_backoff_ms: u64,
+    /// How edges are mapped to log records with the `otlp` transport.
+    #[serde(default)]
+    pub otlp: OtlpConfig,
+}
+
+impl CollectorConfig {
+    pub fn validate(&self, path: &str, validator: &mut Validator) {
+        validator.check(
+            format!("{}.cluster", path),
+            !self.cluster.is_empty(),
+            "must name an Envoy cluster",
+        );
+        if let Some(authority) = self.authority.as_deref() {
+            validator.authority(format!("{}.authority", path), authority);
+        }
+        validator.url_path(format!("{}.path", path), self.path());
+        validator.duration_ms(format!("{}.timeout_ms", path), self.timeout_ms);
+        validator.check(
+            format!("{}.batch_size", path),
+            self.batch_size > 0,
+            "must be positive",
+        );
+        validator.duration_ms(
+            format!("{}.flush_interval_ms", path),
+            self.flush_interval_ms,
+        );
+        validator.check(
+            format!("{}.max_buffered_edges", path),
+            self.max_buffered_edges >= self.batch_size,
+            "must be at least batch_size",
+        );
+        validator.duration_ms(
+            format!("{}.initial_backoff_ms", path),
+            self.initial_backoff_ms,
+        );
+        validator.check(
+            format!("{}.max_backoff_ms", path),
+            self.max_backoff_ms >= self.initial_backoff_ms,
+            "must be at least initial_backoff_ms",
+        );
+        self.otlp.validate(&format!("{}.otlp", path), validator);
+    }
+
+    fn path(&self) -> &str {
+        match (self.path.as_deref(), self.transport) {
+            (Some(path), _) => path,
+            (None, Transport::Otlp) => otlp::LOGS_PATH,
+            (None, _) => "/edges",
+        }
+    }
+}
+
+/// Borrowing counterpart of [`dependency_edge::EdgeReport`].
+#[derive(Debug, Serialize)]
+struct EdgeReport<'a> {
+    edges: &'a [ReportedEdge],
+}
+
+struct Batch {
+    edges: Vec<ReportedEdge>,
+    attempts: u32,
+    dispatched_at: SystemTime,
+}
+
+/// Batches learned edges and sends them to the collector.
+///
+/// Http and stream contexts only push edges: reports are sent by the root
+/// context on tick, as the proxy cancels the callouts of a context once its
+/// stream ends. Responses to the root's callouts have to be forwarded to
+/// [`EdgeSink::on_response`] or [`EdgeSink::on_grpc_response`]. A batch
+/// whose response never arrives is treated as failed once its timeout has
+/// elapsed.
+pub struct EdgeSink {
+    config: CollectorConfig,
+    pending: Vec<ReportedEdge>,
+    retries: VecDeque<Batch>,
+    in_flight: HashMap<u32, Batch>,
+    consecutive_failures: u32,
+    backoff_until: Option<SystemTime>,
+}
+
+impl EdgeSink {
+    pub fn new(config: CollectorConfig) -> Self {
+        Self {
+            config,
+            pending: Vec::new(),
+            retries: VecDeque::new(),
+            in_flight: HashMap::new(),
+            consecutive_failures: 0,
+            backoff_until: None,
+        }
+    }
+
+    /// Buffers an edge, unless the buffer is full.
+    pub fn push(&mut self, edge: ReportedEdge) {
+        metrics::increment(Counter::EdgesEmitted);
+        if self.buffered() >= self.config.max_buffered_edges {
+            metrics::increment(Counter::EdgesDropped);
+            return;
+        }
+        self.pending.push(edge);
+    }
+
+    /// Sends batches awaiting a retry, then every pending edge, in batches
+    /// of up to `batch_size`. Nothing is sent while backing off.
+    pub fn flush(&mut self, host: &impl Host, now: SystemTime) {
+        self.expire_in_flight(now);
+        if self.backoff_until.is_some_and(|until| now < until) {
+            return;
+        }
+        self.backoff_until = None;
+
+        while let Some(batch) = self.retries.pop_front() {
+            if !self.dispatch(host, batch, now) {
+                return;
+            }
+        }
+
+        let batch_size = cmp::max(self.config.batch_size, 1);
+        while !self.pending.is_empty() {
+            let len = cmp::min(self.pending.len(), batch_size);
+            let edges = self.pending.drain(..len).collect();
+            let batch = Batch {
+                edges,
+                attempts: 0,
+                dispatched_at: now,
+            };
+            if !self.dispatch(host, batch, now) {
+                return;
+            }
+        }
+    }
+
+    /// Records the outcome of a gRPC report, resending it if `status_code`
+    /// is one that may clear up.
+    pub fn on_grpc_response(&mut self, token_id: u32, status_code: u32, now: SystemTime) {
+        let Some(batch) = self.in_flight.remove(&token_id) else {
+            return;
+        };
+        if status_code == 0 {
+            trace!("Delivered {} edges to collector", batch.edges.len());
+            self.consecutive_failures = 0;
+        } else if RETRYABLE_GRPC_STATUSES.contains(&status_code) {
+            warn!("Collector responded with gRPC status {}", status_code);
+            self.fail(batch, now);
+        } else {
+            metrics::increment(Counter::SinkFailures);
+            metrics::add(Counter::EdgesDropped, batch.edges.len() as u64);
+            error!(
+                "Dropping {} edges rejected with gRPC status {}",
+                batch.edges.len(),
+                status_code
+            );
+        }
+    }
+
+    /// Records the outcome of a report. `status` is the collector's HTTP
+    /// status, or `None` if Envoy failed to reach it at all.
+    pub fn on_response(&mut self, token_id: u32, status: Option<u32>, now: SystemTime) {
+        let Some(batch) = self.in_flight.remove(&token_id) else {
+            return;
+        };
+        match status {
+            Some(status) if (200..300).contains(&status) => {
+                trace!("Delivered {} edges to collector", batch.edges.len());
+                self.consecutive_failures = 0;
+            }
+            Some(status) => {
+                warn!("Collector responded with status {}", status);
+                self.fail(batch, now);
+            }
+            None => {
+                warn!("Collector could not be reached");
+                self.fail(batch, now);
+            }
+        }
+    }
+
+    /// Sends a batch, returning whether the dispatch went through. Batches
+    /// that fail to dispatch count as a failed attempt.
+    fn dispatch(&mut self, host: &impl Host, mut batch: Batch, now: SystemTime) -> bool {
+        let timeout = Duration::from_millis(self.config.timeout_ms);
+        let dispatched = match self.config.transport {
+            Transport::Http | Transport::Otlp => {
+                let body = match self.config.transport {
+                    Transport::Otlp => self.config.otlp.export_logs(&batch.edges),
+                    _ => serde_json::to_vec(&EdgeReport {
+                        edges: &batch.edges,
+                    }),
+                };
+                let body = match body {
+                    Ok(body) => body,
+                    Err(err) => {
+                        metrics::increment(Counter::SinkFailures);
+                        metrics::add(Counter::EdgesDropped, batch.edges.len() as u64);
+                        error!(
+                            "Dropping {} edges that failed to serialize: {}",
+                            batch.edges.len(),
+                            err
+                        );
+                        return true;
+                    }
+                };
+                let authority = self
+                    .config
+                    .authority
+                    .as_deref()
+                    .unwrap_or(&self.config.cluster);
+                host.dispatch_http_call(
+                    &self.config.cluster,
+                    vec![
+                        (":method", "POST"),
+                        (":path", self.config.path()),
+                        (":authority", authority),
+                        ("content-type", "application/json"),
+                    ],
+                    &body,
+                    timeout,
+                )
+            }
+            Transport::Grpc => host.dispatch_grpc_call(
+                &self.config.cluster,
+                EDGE_COLLECTOR_SERVICE,
+                REPORT_METHOD,
+                &proto::EdgeReport::from(&batch.edges[..]).encode_to_vec(),
+                timeout,
+            ),
+        };
+        match dispatched {
+            Ok(token_id) => {
+                batch.dispatched_at = now;
+                self.in_flight.insert(token_id, batch);
+                true
+            }
+            Err(status) => {
+                warn!("Failed to dispatch edge report: {:?}", status);
+                self.fail(batch, now);
+                false
+            }
+        }
+    }
+
+    fn expire_in_flight(&mut self, now: SystemTime) {
+        let timeout = Duration::from_millis(self.config.timeout_ms);
+        let expired: Vec<u32> = self
+            .in_flight
+            .iter()
+            .filter(|(_, batch)| {
+                now.duration_since(batch.dispatched_at)
+                    .is_ok_and(|elapsed| elapsed > timeout)
+            })
+            .map(|(token_id, _)| *token_id)
+            .collect();
+        for token_id in expired {
+            if let Some(batch) = self.in_flight.remove(&token_id) {
+                warn!("Edge report {} timed out without a response", token_id);
+                self.fail(batch, now);
+            }
+        }
+    }
+
+    fn fail(&mut self, mut batch: Batch, now: SystemTime) {
+        metrics::increment(Counter::SinkFailures);
+        batch.attempts += 1;
+        if batch.attempts > self.config.max_retries {
+            metrics::add(Counter::EdgesDropped, batch.edges.len() as u64);
+            error!(
+                "Dropping {} edges after {} failed attempts",
+                batch.edges.len(),
+                batch.attempts
+            );
+        } else if self.buffered() + batch.edges.len() > self.config.max_buffered_edges {
+            metrics::add(Counter::EdgesDropped, batch.edges.len() as u64);
+            error!(
+                "Dropping {} edges to resend, as the buffer is full",
+                batch.edges.len()
+            );
+        } else {
+            self.retries.push_back(batch);
+        }
+        self.back_off(now);
+    }
+
+    /// Edges awaiting their first or another attempt.
+    fn buffered(&self) -> usize {
+        self.pending.len()
+            + self
+                .retries
+                .iter()
+                .map(|batch| batch.edges.len())
+                .sum::<usize>()
+    }
+
+    fn back_off(&mut self, now: SystemTime) {
+        self.consecutive_failures = self.consecutive_failures.saturating_add(1);
+        let exponent = cmp::min(self.consecutive_failures - 1, 16);
+        let backoff = cmp::min(
+            self.config.initial_backoff_ms.saturating_mul(1 << exponent),
+            self.config.max_backoff_ms,
+        );
+        self.backoff_until = Some(now + Duration::from_millis(backoff));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use dependency_edge::{DependencyEdge, Downstream, Request, Upstream};
+    use proxy_wasm::types::Status;
+
+    use super::*;
+    use crate::harness::{metric, FakeHost};
+
+    fn sink(config: &str) -> EdgeSink {
+        EdgeSink::new(serde_json::from_str(config).unwrap())
+    }
+
+    fn reported(path: &str) -> ReportedEdge {
+        ReportedEdge {
+            edge: DependencyEdge {
+                downstream: Downstream::from_uri_sans(
+                    "spiffe://cluster.local/ns/client/sa/default",
+                ),
+                upstream: Upstream::from_cluster("outbound|80||server.server.svc.cluster.local"),
+                request: Request {
+                    path: Some(path.to_string()),
+                    ..Request::default()
+                },
+            },
+            first_seen: 1_700_000_000,
+            last_seen: 1_700_000_000,
+            requests: None,
+            errors: None,
+            outcomes: None,
+        }
+    }
+
+    /// Paths of the edges of each report sent so far.
+    fn reports(host: &FakeHost) -> Vec<Vec<String>> {
+        host.callouts
+            .borrow()
+            .iter()
+            .map(|callout| {
+                let report: dependency_edge::EdgeReport =
+                    serde_json::from_slice(&callout.body).unwrap();
+                report
+                    .edges
+                    .into_iter()
+                    .map(|edge| edge.edge.request.path.unwrap())
+                    .collect()
+            })
+            .collect()
+    }
+
+    fn millis(host: &FakeHost, millis: u64) -> SystemTime {
+        host.now + Duration::from_millis(millis)
+    }
+
+    #[test]
+    fn resends_batch_after_5xx() {
+        let mut sink = sink(r#"{"cluster": "collector"}"#);
+        let host = FakeHost::default();
+        sink.push(reported("/a"));
+        sink.flush(&host, host.now);
+        sink.on_response(0, Some(503), host.now);
+        sink.flush(&host, millis(&host, 500));
+        assert_eq!(reports(&host), [["/a"], ["/a"]]);
+
+        sink.on_response(1, Some(200), millis(&host, 500));
+        sink.flush(&host, millis(&host, 1_000));
+        assert_eq!(reports(&host).len(), 2);
+    }
+
+    #[test]
+    fn sends_nothing_while_backing_off() {
+        let mut sink = sink(r#"{"cluster": "collector"}"#);
+        let host = FakeHost::default();
+        sink.push(reported("/a"));
+        sink.flush(&host, host.now);
+        sink.on_response(0, None, host.now);
+        sink.push(reported("/b"));
+        sink.flush(&host, millis(&host, 499));
+        assert_eq!(reports(&host).len(), 1);
+        sink.flush(&host, millis(&host, 500));
+        assert_eq!(reports(&host), [vec!["/a"], vec!["/a"], vec!["/b"]]);
+
+        // The backoff doubles with consecutive failures.
+        sink.on_response(1, Some(500), millis(&host, 500));
+        sink.flush(&host, millis(&host, 1_499));
+        assert_eq!(reports(&host).len(), 3);
+        sink.flush(&host, millis(&host, 1_500));
+        assert_eq!(reports(&host).len(), 4);
+    }
+
+    #[test]
+    fn drops_batch_after_max_retries() {
+        let dropped = metric("dependency_learner.edges_dropped");
+        let mut sink = sink(r#"{"cluster": "collector", "max_retries": 1}"#);
+        let host = FakeHost::default();
+        sink.push(reported("/a"));
+        sink.flush(&host, host.now);
+        sink.on_response(0, Some(503), host.now);
+        sink.flush(&host, millis(&host, 500));
+        sink.on_response(1, Some(503), millis(&host, 500));
+        sink.flush(&host, millis(&host, 60_000));
+        assert_eq!(reports(&host).len(), 2);
+        assert_eq!(metric("dependency_learner.edges_dropped"), dropped + 1);
+    }
+
+    #[test]
+    fn resends_batch_without_response_after_timeout() {
+        let mut sink = sink(r#"{"cluster": "collector", "timeout_ms": 1000}"#);
+        let host = FakeHost::default();
+        sink.push(reported("/a"));
+        sink.flush(&host, host.now);
+        sink.flush(&host, millis(&host, 1_000));
+        assert_eq!(reports(&host).len(), 1);
+        // Expiring the report backs off before it is resent.
+        sink.flush(&host, millis(&host, 1_001));
+        assert_eq!(reports(&host).len(), 1);
+        sink.flush(&host, millis(&host, 1_501));
+        assert_eq!(reports(&host), [["/a"], ["/a"]]);
+
+        // A response to the expired report is ignored.
+        sink.on_response(0, Some(200), millis(&host, 1_501));
+        sink.on_response(1, Some(200), millis(&host, 1_501));
+        sink.flush(&host, millis(&host, 60_000));
+        assert_eq!(reports(&host).len(), 2);
+    }
+
+    #[test]
+    fn counts_failed_dispatch_as_attempt() {
+        let dropped = metric("dependency_learner.edges_dropped");
+        let mut sink = sink(r#"{"cluster": "collector", "max_retries": 2}"#);
+        let host = FakeHost::default();
+        host.dispatch_failure.set(Some(Status::BadArgument));
+        sink.push(reported("/a"));
+        for at in [0, 500, 1_500] {
+            sink.flush(&host, millis(&host, at));
+        }
+        assert_eq!(metric("dependency_learner.edges_dropped"), dropped + 1);
+
+        host.dispatch_failure.set(None);
+        sink.flush(&host, millis(&host, 60_000));
+        assert!(reports(&host).is_empty());
+    }
+
+    #[test]
+    fn drops_edges_beyond_buffer() {
+        let dropped = metric("dependency_learner.edges_dropped");
+        let mut sink =
+            sink(r#"{"cluster": "collector", "batch_size": 2, "max_buffered_edges": 2}"#);
+        let host = FakeHost::default();
+        sink.push(reported("/a"));
+        sink.flush(&host, host.now);
+        sink.on_response(0, Some(503), host.now);
+        // The batch to resend fills the buffer.
+        sink.push(reported("/b"));
+        sink.push(reported("/c"));
+        assert_eq!(metric("dependency_learner.edges_dropped"), dropped + 1);
+        sink.flush(&host, millis(&host, 500));
+        assert_eq!(reports(&host), [vec!["/a"], vec!["/a"], vec!["/b"]]);
+    }
+
+    #[test]
+    fn reports_to_grpc_collector() {
+        let mut sink = sink(r#"{"cluster": "collector", "transport": "grpc"}"#);
+        let host = FakeHost::default();
+        sink.push(reported("/a"));
+        sink.flush(&host, host.now);
+        let callouts = host.callouts.borrow().clone();
+        assert_eq!(callouts.len(), 1);
+        assert_eq!(
+            (callouts[0].cluster.as_str(), callouts[0].path.as_str()),
+            ("collector", "dependency_learner.v1.EdgeCollector/Report")
+        );
+        let report = proto::EdgeReport::decode(callouts[0].body.as_slice()).unwrap();
+        let report = dependency_edge::EdgeReport::from(report);
+        assert_eq!(report.edges.len(), 1);
+        assert_eq!(report.edges[0].edge, reported("/a").edge);
+    }
+
+    #[test]
+    fn resends_only_retryable_grpc_failures() {
+        let dropped = metric("dependency_learner.edges_dropped");
+        let mut sink = sink(r#"{"cluster": "collector", "transport": "grpc"}"#);
+        let host = FakeHost::default();
+        sink.push(reported("/a"));
+        sink.flush(&host, host.now);
+        // UNAVAILABLE
+        sink.on_grpc_response(0, 14, host.now);
+        sink.flush(&host, millis(&host, 500));
+        assert_eq!(host.callouts.borrow().len(), 2);
+
+        // INVALID_ARGUMENT
+        sink.on_grpc_response(1, 3, millis(&host, 500));
+        sink.flush(&host, millis(&host, 60_000));
+        assert_eq!(host.callouts.borrow().len(), 2);
+        assert_eq!(metric("dependency_learner.edges_dropped"), dropped + 1);
+    }
+}
@@ -0,0 +1,187 @@
+use std::{cell::RefCell, rc::Rc};
+
+use dependency_edge::{DependencyEdge, Downstream, Protocol, Request, Upstream};
+use proxy_wasm::{
+    traits::{Context, StreamContext},
+    types::{Action, PeerType},
+};
+
+use crate::{
+    aggregate::EdgeObservation,
+    emit::EdgeEmitter,
+    host::{Host, Proxy},
+    identity,
+    metrics::{self, Counter},
+    sink::EdgeSink,
+    string_property, DependencyLearnerConfig,
+};
+
+/// Learns an edge per TCP connection, for services routed through the
+/// gateway without http, e.g. databases or TLS passthrough. Runs against the
+/// proxy, or against the host it is given in tests.
+pub struct DependencyLearnerStream<H = Proxy> {
+    host: H,
+    notified: bool,
+    pub(crate) downstream: Option<Downstream>,
+    pub(crate) sni: Option<String>,
+    pub(crate) upstream_cluster: Option<String>,
+    upstream_data: bool,
+    emitter: EdgeEmitter,
+}
+
+impl DependencyLearnerStream {
+    pub fn new(
+        config: DependencyLearnerConfig,
+        sink: Option<Rc<RefCell<EdgeSink>>>,
+        queue_id: Option<u32>,
+    ) -> Self {
+        Self::with_host(Proxy, config, sink, queue_id)
+    }
+}
+
+impl<H: Host + Copy> DependencyLearnerStream<H> {
+    pub(crate) fn with_host(
+        host: H,
+        config: DependencyLearnerConfig,
+        sink: Option<Rc<RefCell<EdgeSink>>>,
+        queue_id: Option<u32>,
+    ) -> Self {
+        Self {
+            host,
+            emitter: EdgeEmitter::new(&config, sink, queue_id),
+            notified: false,
+            downstream: None,
+            sni: None,
+            upstream_cluster: None,
+            upstream_data: false,
+        }
+    }
+
+    /// The upstream cluster is picked by the tcp proxy, which runs after this
+    /// filter, so it may only be known once data flows.
+    fn resolve_upstream_cluster(&mut self, host: &impl Host) {
+        if self.upstream_cluster.is_none() {
+            self.upstream_cluster = string_property(host, &["xds", "cluster_name"]);
+        }
+    }
+
+    /// Captures the downstream identity and SNI of a new connection.
+    pub(crate) fn on_connection(&mut self, host: &impl Host) {
+        metrics::increment(Counter::RequestsObserved);
+        self.downstream = identity::peer_certificate(host);
+        self.sni = string_property(host, &["connection", "requested_server_name"])
+            .filter(|sni| !sni.is_empty());
+        self.resolve_upstream_cluster(host);
+    }
+
+    /// Returns the edge once the upstream cluster is known, or when the
+    /// connection ends without it being known.
+    pub(crate) fn on_event(&mut self, host: &impl Host, closing: bool) -> Option<EdgeObservation> {
+        self.resolve_upstream_cluster(host);
+        if self.notified || (self.upstream_cluster.is_none() && !closing) {
+            return None;
+        }
+        let edge = DependencyEdge {
+            downstream: self.downstream.clone().unwrap_or_default(),
+            upstream: self
+                .upstream_cluster
+                .as_deref()
+                .map(Upstream::from_cluster)
+                .unwrap_or_default(),
+            request: Request {
+                protocol: Some(Protocol::Tcp),
+                sni: self.sni.clone(),
+                ..Request::default()
+            },
+        };
+        // A connection that never heard back from its upstream failed.
+        let error = closing && !self.upstream_data;
+        self.notified = true;
+        Some(EdgeObservation {
+            edge,
+            error,
+            outcome: None,
+        })
+    }
+
+    fn notify(&mut self, closing: bool) {
+        let host = self.host;
+        if let Some(observation) = self.on_event(&host, closing) {
+            self.emitter.emit(&host, observation);
+        }
+    }
+}
+
+impl<H: Host + Copy> Context for DependencyLearnerStream<H> {}
+
+impl<H: Host + Copy> StreamContext for DependencyLearnerStream<H> {
+    fn on_new_connection(&mut self) -> Action {
+        let host = self.host;
+        self.on_connection(&host);
+        Action::Continue
+    }
+
+    fn on_downstream_data(&mut self, _data_size: usize, _end_of_stream: bool) -> Action {
+        let host = self.host;
+        self.resolve_upstream_cluster(&host);
+        Action::Continue
+    }
+
+    fn on_upstream_data(&mut self, _data_size: usize, _end_of_stream: bool) -> Action {
+        self.upstream_data = true;
+        self.notify(false);
+        Action::Continue
+    }
+
+    fn on_downstream_close(&mut self, _peer_type: PeerType) {
+        self.notify(true);
+    }
+
+    fn on_upstream_close(&mut self, _peer_type: PeerType) {
+        self.notify(true);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::UNIX_EPOCH;
+
+    use dependency_edge::EdgeReport;
+
+    use super::*;
+    use crate::{harness::FakeHost, DependencyLearnerRoot};
+
+    #[test]
+    fn reports_connection_edge_once_through_callbacks() {
+        let config: DependencyLearnerConfig = serde_json::from_str(
+            r#"{"version": 1, "filter_type": "network", "collector": {"cluster": "collector"}}"#,
+        )
+        .unwrap();
+        let mut root = DependencyLearnerRoot::new();
+        root.configure(config.clone());
+        let host = FakeHost::default();
+        host.mtls("spiffe://cluster.local/ns/client/sa/default");
+        let mut stream = DependencyLearnerStream::with_host(&host, config, root.sink.clone(), None);
+
+        stream.on_new_connection();
+        host.set_property("xds.cluster_name", b"outbound|5432||db.example.com");
+        stream.on_downstream_data(64, false);
+        stream.on_upstream_data(64, false);
+        stream.on_downstream_close(PeerType::Remote);
+        stream.on_upstream_close(PeerType::Remote);
+
+        root.send_edges(&host, host.now);
+        let callouts = host.callouts.borrow().clone();
+        assert_eq!(callouts.len(), 1);
+        let report: EdgeReport = serde_json::from_slice(&callouts[0].body).unwrap();
+        assert_eq!(report.edges.len(), 1);
+        let reported = &report.edges[0];
+        assert_eq!(reported.edge.request.protocol, Some(Protocol::Tcp));
+        assert_eq!(
+            reported.edge.upstream.host.as_deref(),
+            Some("db.example.com")
+        );
+        let now = host.now.duration_since(UNIX_EPOCH).unwrap().as_secs();
+        assert_eq!((reported.first_seen, reported.last_seen), (now, now));
+    }
+}
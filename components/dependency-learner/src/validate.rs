@@ -0,0 +1,120 @@
+use std::fmt;
+
+use dependency_edge::SpiffeId;
+use serde::de::DeserializeOwned;
+
+/// A problem with a configuration, and where it is.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConfigError {
+    /// Path of the offending field, e.g. `collector.timeout_ms` or
+    /// `identity_sources[1].name`.
+    pub path: String,
+    pub message: String,
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.path, self.message)
+    }
+}
+
+/// Renders errors as a single log line.
+pub fn describe(errors: &[ConfigError]) -> String {
+    errors
+        .iter()
+        .map(ConfigError::to_string)
+        .collect::<Vec<_>>()
+        .join("; ")
+}
+
+/// Parses JSON, reporting where parsing failed.
+pub fn parse<T: DeserializeOwned>(raw: &[u8]) -> Result<T, ConfigError> {
+    let deserializer = &mut serde_json::Deserializer::from_slice(raw);
+    serde_path_to_error::deserialize(deserializer).map_err(|err| {
+        let path = err.path().to_string();
+        ConfigError {
+            path: if path == "." {
+                "<root>".to_string()
+            } else {
+                path
+            },
+            message: err.into_inner().to_string(),
+        }
+    })
+}
+
+/// Collects the semantic errors of a parsed configuration.
+#[derive(Debug, Default)]
+pub struct Validator {
+    errors: Vec<ConfigError>,
+}
+
+impl Validator {
+    pub fn into_errors(self) -> Vec<ConfigError> {
+        self.errors
+    }
+
+    pub fn check(&mut self, path: impl Into<String>, valid: bool, message: impl Into<String>) {
+        if !valid {
+            self.errors.push(ConfigError {
+                path: path.into(),
+                message: message.into(),
+            });
+        }
+    }
+
+    /// Envoy matches header names in lowercase, so names with uppercase
+    /// letters would never match.
+    pub fn header_name(&mut self, path: impl Into<String>, name: &str) {
+        let valid = !name.is_empty()
+            && name.bytes().all(|b| {
+                b.is_ascii_lowercase() || b.is_ascii_digit() || b"!#$%&'*+-.^_`|~".contains(&b)
+            });
+        self.check(
+            path,
+            valid,
+            format!("{:?} is not a lowercase http header name", name),
+        );
+    }
+
+    pub fn duration_ms(&mut self, path: impl Into<String>, ms: u64) {
+        self.check(path, ms > 0, "must be a positive number of milliseconds");
+    }
+
+    /// An origin-form request target, i.e. an absolute path and optional
+    /// query.
+    pub fn url_path(&mut self, path: impl Into<String>, url_path: &str) {
+        let valid = url_path.starts_with('/')
+            && url_path.bytes().all(|b| b.is_ascii_graphic())
+            && !url_path.contains('#');
+        self.check(
+            path,
+            valid,
+            format!("{:?} is not a path starting with /", url_path),
+        );
+    }
+
+    /// A `host[:port]` authority.
+    pub fn authority(&mut self, path: impl Into<String>, authority: &str) {
+        let (host, port) = match authority.rsplit_once(':') {
+            Some((host, port)) if !port.contains(']') => (host, Some(port)),
+            _ => (authority, None),
+        };
+        let valid = !host.is_empty()
+            && host
+                .bytes()
+                .all(|b| b.is_ascii_alphanumeric() || b"-._~[]:".contains(&b))
+            && port.is_none_or(|port| port.parse::<u16>().is_ok());
+        self.check(
+            path,
+            valid,
+            format!("{:?} is not a host with an optional port", authority),
+        );
+    }
+
+    pub fn spiffe_id(&mut self, path: impl Into<String>, id: &str) {
+        if let Err(err) = SpiffeId::parse(id) {
+            self.check(path, false, format!("{:?} is not a SPIFFE ID: {}", id, err));
+        }
+    }
+}
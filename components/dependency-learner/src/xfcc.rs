@@ -0,0 +1,71 @@
+//! The `x-forwarded-client-cert` header, in the format Envoy writes it:
+//! comma separated elements, one per proxy hop, each made of semicolon
+//! separated `Key=Value` pairs whose values may be double quoted.
+
+/// One element of the header, describing the client certificate a proxy hop
+/// saw.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct XfccElement {
+    pairs: Vec<(String, String)>,
+}
+
+impl XfccElement {
+    /// Values of a key, e.g. every `URI` SAN. Keys are case insensitive.
+    pub fn values<'a>(&'a self, key: &'a str) -> impl Iterator<Item = &'a str> + 'a {
+        self.pairs
+            .iter()
+            .filter(move |(name, _)| name.eq_ignore_ascii_case(key))
+            .map(|(_, value)| value.as_str())
+    }
+}
+
+/// Parses the header. Returns `None` if it is malformed.
+pub fn parse(header: &str) -> Option<Vec<XfccElement>> {
+    let mut elements = Vec::new();
+    let mut element = XfccElement::default();
+    let mut chars = header.chars().peekable();
+    loop {
+        let mut key = String::new();
+        loop {
+            match chars.next()? {
+                '=' => break,
+                c => key.push(c),
+            }
+        }
+        let key = key.trim();
+        if key.is_empty() {
+            return None;
+        }
+
+        let mut value = String::new();
+        if chars.peek() == Some(&'"') {
+            chars.next();
+            loop {
+                match chars.next()? {
+                    '"' => break,
+                    '\\' => value.push(chars.next()?),
+                    c => value.push(c),
+                }
+            }
+        } else {
+            while let Some(&c) = chars.peek() {
+                if c == ';' || c == ',' {
+                    break;
+                }
+                value.push(c);
+                chars.next();
+            }
+        }
+        element.pairs.push((key.to_string(), value));
+
+        match chars.next() {
+            Some(';') => {}
+            Some(',') => elements.push(std::mem::take(&mut element)),
+            None => {
+                elements.push(element);
+                return Some(elements);
+            }
+            Some(_) => return None,
+        }
+    }
+}
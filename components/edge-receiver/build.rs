@@ -0,0 +1,13 @@
+use dependency_edge::proto::FILE_DESCRIPTOR_SET;
+use prost::Message;
+
+/// Generates the `EdgeCollector` client and server from `edges.proto`, around
+/// the prost messages `dependency-edge` generates from it.
+fn main() {
+    let files = prost_types::FileDescriptorSet::decode(FILE_DESCRIPTOR_SET)
+        .expect("failed to decode edges.proto descriptors");
+    tonic_prost_build::configure()
+        .extern_path(".dependency_learner.v1", "::dependency_edge::proto")
+        .compile_fds(files)
+        .expect("failed to generate the EdgeCollector service");
+}
@@ -0,0 +1,72 @@
+//! A minimal `dependency_learner.v1.EdgeCollector`, receiving the edges the
+//! dependency-learner filter reports over gRPC. Meant for local development
+//! and tests of the gRPC sink rather than for production.
+
+use std::sync::{Arc, Mutex};
+
+use dependency_edge::{proto, ReportedEdge};
+use tokio::net::TcpListener;
+use tonic::{
+    transport::{server::TcpIncoming, Server},
+    Code, Request, Response, Status,
+};
+
+pub mod pb {
+    tonic::include_proto!("dependency_learner.v1");
+}
+
+pub use pb::{
+    edge_collector_client::EdgeCollectorClient, edge_collector_server::EdgeCollectorServer,
+};
+
+/// Keeps the edges it receives, or rejects reports with a fixed status to
+/// exercise the retries of the sink.
+#[derive(Debug, Clone, Default)]
+pub struct EdgeReceiver {
+    edges: Arc<Mutex<Vec<ReportedEdge>>>,
+    fail_with: Option<Code>,
+}
+
+impl EdgeReceiver {
+    /// A receiver answering every report with `code` instead of accepting it.
+    pub fn failing(code: Code) -> Self {
+        Self {
+            fail_with: Some(code),
+            ..Self::default()
+        }
+    }
+
+    /// Edges received so far, in order.
+    pub fn edges(&self) -> Vec<ReportedEdge> {
+        self.edges.lock().unwrap().clone()
+    }
+}
+
+#[tonic::async_trait]
+impl pb::edge_collector_server::EdgeCollector for EdgeReceiver {
+    async fn report(
+        &self,
+        request: Request<proto::EdgeReport>,
+    ) -> Result<Response<proto::ReportResponse>, Status> {
+        if let Some(code) = self.fail_with {
+            return Err(Status::new(code, "edge receiver configured to fail"));
+        }
+        let report = dependency_edge::EdgeReport::from(request.into_inner());
+        for edge in &report.edges {
+            log::info!("{}", serde_json::to_string(edge).unwrap_or_default());
+        }
+        self.edges.lock().unwrap().extend(report.edges);
+        Ok(Response::new(proto::ReportResponse {}))
+    }
+}
+
+/// Serves `receiver` on `listener` until the server fails.
+pub async fn serve(
+    listener: TcpListener,
+    receiver: EdgeReceiver,
+) -> Result<(), tonic::transport::Error> {
+    Server::builder()
+        .add_service(EdgeCollectorServer::new(receiver))
+        .serve_with_incoming(TcpIncoming::from(listener))
+        .await
+}
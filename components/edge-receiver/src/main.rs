@@ -0,0 +1,30 @@
+use std::net::SocketAddr;
+
+use anyhow::{Context, Result};
+use clap::Parser;
+use edge_receiver::EdgeReceiver;
+use log::info;
+use tokio::net::TcpListener;
+
+/// Receives the edges the dependency-learner filter reports over gRPC and
+/// logs them as JSON lines.
+#[derive(Debug, Parser)]
+struct Args {
+    /// Address to accept `dependency_learner.v1.EdgeCollector` calls on.
+    #[arg(long, default_value = "127.0.0.1:9090")]
+    listen: SocketAddr,
+}
+
+#[tokio::main]
+async fn main() -> Result<()> {
+    env_logger::Builder::from_env(env_logger::Env::default().default_filter_or("info")).init();
+    let args = Args::parse();
+
+    let listener = TcpListener::bind(args.listen)
+        .await
+        .with_context(|| format!("failed to listen on {}", args.listen))?;
+    info!("receiving edges on {}", args.listen);
+    edge_receiver::serve(listener, EdgeReceiver::default())
+        .await
+        .context("edge receiver failed")
+}
@@ -0,0 +1,59 @@
+use dependency_edge::{proto, DependencyEdge, Downstream, ReportedEdge, Upstream};
+use edge_receiver::{EdgeCollectorClient, EdgeReceiver};
+use tokio::net::TcpListener;
+use tonic::{transport::Channel, Code};
+
+async fn start(receiver: EdgeReceiver) -> EdgeCollectorClient<Channel> {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(edge_receiver::serve(listener, receiver));
+    EdgeCollectorClient::connect(format!("http://{}", addr))
+        .await
+        .unwrap()
+}
+
+fn edges() -> Vec<ReportedEdge> {
+    vec![ReportedEdge {
+        edge: DependencyEdge {
+            downstream: Downstream::from_uri_sans("spiffe://cluster.local/ns/client/sa/default"),
+            upstream: Upstream::from_cluster("outbound|80||server.server.svc.cluster.local"),
+            ..DependencyEdge::default()
+        },
+        first_seen: 1_700_000_000,
+        last_seen: 1_700_000_060,
+        requests: Some(3),
+        errors: Some(1),
+        outcomes: None,
+    }]
+}
+
+#[tokio::test]
+async fn keeps_reported_edges() {
+    let receiver = EdgeReceiver::default();
+    let mut client = start(receiver.clone()).await;
+
+    client
+        .report(proto::EdgeReport::from(&edges()[..]))
+        .await
+        .unwrap();
+
+    let received = receiver.edges();
+    assert_eq!(received.len(), 1);
+    assert_eq!(received[0].edge, edges()[0].edge);
+    assert_eq!(received[0].requests, Some(3));
+    assert_eq!(received[0].errors, Some(1));
+}
+
+#[tokio::test]
+async fn fails_reports_when_configured_to() {
+    let receiver = EdgeReceiver::failing(Code::Unavailable);
+    let mut client = start(receiver.clone()).await;
+
+    let status = client
+        .report(proto::EdgeReport::from(&edges()[..]))
+        .await
+        .unwrap_err();
+
+    assert_eq!(status.code(), Code::Unavailable);
+    assert!(receiver.edges().is_empty());
+}
@@ -3,10 +3,12 @@ package istio
 import (
 	"bytes"
 	"context"
+	"encoding/json"
 	"fmt"
 	"path"
 	"strings"
 	"testing"
+	"time"
 
 	"github.com/stretchr/testify/assert"
 	"google.golang.org/protobuf/types/known/structpb"
@@ -18,21 +20,31 @@ import (
 	istioscheme "istio.io/client-go/pkg/clientset/versioned/scheme"
 	appsv1 "k8s.io/api/apps/v1"
 	corev1 "k8s.io/api/core/v1"
+	rbacv1 "k8s.io/api/rbac/v1"
 	metav1 "k8s.io/apimachinery/pkg/apis/meta/v1"
 	"sigs.k8s.io/e2e-framework/klient/k8s/resources"
 	"sigs.k8s.io/e2e-framework/klient/wait"
 	"sigs.k8s.io/e2e-framework/klient/wait/conditions"
 	"sigs.k8s.io/e2e-framework/pkg/envconf"
 	"sigs.k8s.io/e2e-framework/pkg/features"
+	"sigs.k8s.io/yaml"
 )
 
-const dependencyLearnerComponentLabelValue = "dependency-learner"
+const (
+	dependencyLearnerComponentLabelValue = "dependency-learner"
+	edgeCollectorPort                    = 8080
+	edgeCollectorPath                    = "/edges"
+	learnedDependenciesConfigMap         = "learned-dependencies"
+)
 
 func TestDependencyLearner(t *testing.T) {
 	clientNamespace := envconf.RandomName("client", 16)
 	serverNamespace := envconf.RandomName("server", 16)
+	collectorNamespace := envconf.RandomName("collector", 16)
 	clientName := "client"
 	serverName := "server"
+	collectorName := "dependency-controller"
+	collectorContainerName := "controller"
 	containerName := "testapp"
 	fallbackName := envconf.RandomName("fallback", 16)
 	responseHeader := "detected-dependency"
@@ -75,6 +87,132 @@ func TestDependencyLearner(t *testing.T) {
 					return ctx
 				}
 
+				// create collector namespace
+				collectorNamespaceObj := &corev1.Namespace{
+					ObjectMeta: metav1.ObjectMeta{
+						Name: collectorNamespace,
+					},
+				}
+				if err := r.Create(ctx, collectorNamespaceObj); !assert.NoError(t, err) {
+					return ctx
+				}
+
+				// grant dependency controller access to configmaps, pods and sidecars
+				collectorSaObj := &corev1.ServiceAccount{
+					ObjectMeta: metav1.ObjectMeta{
+						Name:      collectorName,
+						Namespace: collectorNamespace,
+					},
+				}
+				if err := r.Create(ctx, collectorSaObj); !assert.NoError(t, err) {
+					return ctx
+				}
+				collectorRoleObj := &rbacv1.ClusterRole{
+					ObjectMeta: metav1.ObjectMeta{
+						Name: collectorNamespace,
+					},
+					Rules: []rbacv1.PolicyRule{
+						{
+							APIGroups: []string{""},
+							Resources: []string{"configmaps"},
+							Verbs:     []string{"get", "create", "update"},
+						},
+						{
+							APIGroups: []string{""},
+							Resources: []string{"pods"},
+							Verbs:     []string{"list"},
+						},
+						{
+							APIGroups: []string{"networking.istio.io"},
+							Resources: []string{"sidecars"},
+							Verbs:     []string{"get", "create", "patch"},
+						},
+					},
+				}
+				if err := r.Create(ctx, collectorRoleObj); !assert.NoError(t, err) {
+					return ctx
+				}
+				collectorRoleBindingObj := &rbacv1.ClusterRoleBinding{
+					ObjectMeta: metav1.ObjectMeta{
+						Name: collectorNamespace,
+					},
+					RoleRef: rbacv1.RoleRef{
+						APIGroup: rbacv1.GroupName,
+						Kind:     "ClusterRole",
+						Name:     collectorRoleObj.Name,
+					},
+					Subjects: []rbacv1.Subject{
+						{
+							Kind:      rbacv1.ServiceAccountKind,
+							Name:      collectorSaObj.Name,
+							Namespace: collectorNamespace,
+						},
+					},
+				}
+				if err := r.Create(ctx, collectorRoleBindingObj); !assert.NoError(t, err) {
+					return ctx
+				}
+
+				// deploy dependency controller as edge collector
+				collectorLabels := map[string]string{
+					"app": collectorName,
+				}
+				collectorReplicas := int32(1)
+				collectorDeploymentObj := &appsv1.Deployment{
+					ObjectMeta: metav1.ObjectMeta{
+						Name:      collectorName,
+						Namespace: collectorNamespace,
+					},
+					Spec: appsv1.DeploymentSpec{
+						Replicas: &collectorReplicas,
+						Selector: &metav1.LabelSelector{
+							MatchLabels: collectorLabels,
+						},
+						Template: corev1.PodTemplateSpec{
+							ObjectMeta: metav1.ObjectMeta{
+								Labels: collectorLabels,
+							},
+							Spec: corev1.PodSpec{
+								ServiceAccountName: collectorSaObj.Name,
+								Containers: []corev1.Container{
+									{
+										Name:            collectorContainerName,
+										Image:           dependencyControllerImage,
+										ImagePullPolicy: corev1.PullNever,
+										Args: []string{
+											"--listen", fmt.Sprintf("0.0.0.0:%d", edgeCollectorPort),
+											"--path", edgeCollectorPath,
+										},
+									},
+								},
+							},
+						},
+					},
+				}
+				if err := r.Create(ctx, collectorDeploymentObj); !assert.NoError(t, err) {
+					return ctx
+				}
+
+				// create service for edge collector
+				collectorSvcObj := &corev1.Service{
+					ObjectMeta: metav1.ObjectMeta{
+						Name:      collectorName,
+						Namespace: collectorNamespace,
+					},
+					Spec: corev1.ServiceSpec{
+						Ports: []corev1.ServicePort{
+							{
+								Name: "http",
+								Port: edgeCollectorPort,
+							},
+						},
+						Selector: collectorLabels,
+					},
+				}
+				if err := r.Create(ctx, collectorSvcObj); !assert.NoError(t, err) {
+					return ctx
+				}
+
 				// setup gateway
 				fallbackGatewayObj := &apinetworkingv1alpha3.Gateway{
 					ObjectMeta: metav1.ObjectMeta{
@@ -151,7 +289,15 @@ func TestDependencyLearner(t *testing.T) {
 
 				// deploy wasm plugin
 				pluginConfig, err := structpb.NewStruct(map[string]interface{}{
+					"version":         1,
 					"response_header": responseHeader,
+					"collector": map[string]interface{}{
+						"cluster": fmt.Sprintf(
+							"outbound|%d||%s.%s.svc.cluster.local",
+							edgeCollectorPort, collectorName, collectorNamespace,
+						),
+						"path": edgeCollectorPath,
+					},
 				})
 				if !assert.NoError(t, err) {
 					return ctx
@@ -374,12 +520,26 @@ func TestDependencyLearner(t *testing.T) {
 					return ctx
 				}
 
-				if !assert.Contains(t, stdoutStr, fmt.Sprintf(
-					"%s: spiffe://cluster.local/ns/%s/sa/default -> outbound|80||%s.%s.svc.cluster.local",
-					responseHeader, clientNamespace, serverName, serverNamespace,
-				)) {
+				var edgeHeader string
+				for _, line := range strings.Split(stdoutStr, "\n") {
+					name, value, found := strings.Cut(strings.TrimSpace(line), ":")
+					if found && strings.EqualFold(name, responseHeader) {
+						edgeHeader = strings.TrimSpace(value)
+					}
+				}
+				var edge struct {
+					Downstream struct {
+						Principal string `json:"principal"`
+					} `json:"downstream"`
+					Upstream struct {
+						Cluster string `json:"cluster"`
+					} `json:"upstream"`
+				}
+				if !assert.NoError(t, json.Unmarshal([]byte(edgeHeader), &edge)) {
 					return ctx
 				}
+				assert.Equal(t, fmt.Sprintf("spiffe://cluster.local/ns/%s/sa/default", clientNamespace), edge.Downstream.Principal)
+				assert.Equal(t, fmt.Sprintf("outbound|80||%s.%s.svc.cluster.local", serverName, serverNamespace), edge.Upstream.Cluster)
 
 				return ctx
 			},
@@ -387,7 +547,39 @@ func TestDependencyLearner(t *testing.T) {
 		Assess(
 			"check config map to see if dependency updated",
 			func(ctx context.Context, t *testing.T, c *envconf.Config) context.Context {
-				t.Skip()
+				client, err := c.NewClient()
+				if !assert.NoError(t, err) {
+					return ctx
+				}
+
+				type edgeStats struct {
+					FirstSeen uint64 `json:"first_seen"`
+					LastSeen  uint64 `json:"last_seen"`
+				}
+				var learned map[string]edgeStats
+				configMap := &corev1.ConfigMap{}
+				err = wait.For(func(ctx context.Context) (bool, error) {
+					if err := client.Resources(clientNamespace).Get(ctx, learnedDependenciesConfigMap, clientNamespace, configMap); err != nil {
+						t.Logf("configmap %s not available yet: %v", learnedDependenciesConfigMap, err)
+						return false, nil
+					}
+					raw, ok := configMap.Data["default"]
+					return ok, yaml.Unmarshal([]byte(raw), &learned)
+				}, wait.WithContext(ctx), wait.WithTimeout(time.Minute), wait.WithInterval(time.Second))
+				if !assert.NoError(t, err) {
+					return ctx
+				}
+				t.Logf("learned dependencies:\n%v", configMap.Data)
+
+				// exactly one edge arrives, as the filter deduplicates repeated requests
+				if !assert.Len(t, configMap.Data, 1) || !assert.Len(t, learned, 1) {
+					return ctx
+				}
+				stats, ok := learned[fmt.Sprintf("outbound|80||%s.%s.svc.cluster.local", serverName, serverNamespace)]
+				if !assert.True(t, ok) {
+					return ctx
+				}
+				assert.LessOrEqual(t, stats.FirstSeen, stats.LastSeen)
 				return ctx
 			},
 		).
@@ -414,6 +606,24 @@ func TestDependencyLearner(t *testing.T) {
 					},
 				}))
 
+				assert.NoError(t, r.Delete(ctx, &corev1.Namespace{
+					ObjectMeta: metav1.ObjectMeta{
+						Name: collectorNamespace,
+					},
+				}))
+
+				assert.NoError(t, r.Delete(ctx, &rbacv1.ClusterRoleBinding{
+					ObjectMeta: metav1.ObjectMeta{
+						Name: collectorNamespace,
+					},
+				}))
+
+				assert.NoError(t, r.Delete(ctx, &rbacv1.ClusterRole{
+					ObjectMeta: metav1.ObjectMeta{
+						Name: collectorNamespace,
+					},
+				}))
+
 				assert.NoError(t, r.Delete(ctx, &apiextensionsv1alpha1.WasmPlugin{
 					ObjectMeta: metav1.ObjectMeta{
 						Namespace: istioNamespace,
@@ -39,6 +39,7 @@ const (
 	dependencyLearnerWasmRelativePath string = "target/wasm32-wasi/release/dependency_learner.wasm"
 	dependencyLearnerVolumeName       string = "dependency-learner"
 	nginxVersion                      string = "1.25.5"
+	dependencyControllerImage         string = "l7router/dependency-controller:dev"
 )
 
 func TestMain(m *testing.M) {
@@ -54,6 +55,7 @@ func TestMain(m *testing.M) {
 		envfuncs.LoadDockerImageToCluster(kindClusterName, fmt.Sprintf("istio/proxyv2:%s", istioVersion)),
 		envfuncs.LoadDockerImageToCluster(kindClusterName, fmt.Sprintf("istio/pilot:%s", istioVersion)),
 		envfuncs.LoadDockerImageToCluster(kindClusterName, fmt.Sprintf("nginx:%s", nginxVersion)),
+		envfuncs.LoadDockerImageToCluster(kindClusterName, dependencyControllerImage),
 
 		func(ctx context.Context, c *envconf.Config) (context.Context, error) {
 			manager := helm.New(c.KubeconfigFile())